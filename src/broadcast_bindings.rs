@@ -0,0 +1,68 @@
+use std::collections::HashMap;
+
+use crate::types::Binding;
+
+/// Broadcast bindings indexed by event name, so that a broadcast only reaches the bindings
+/// registered for its event. Bindings registered with a pattern (`*`, `cursor.*`) are kept apart
+/// and matched against every event.
+#[derive(Default)]
+pub(crate) struct BroadcastBindings {
+    by_event: HashMap<String, Vec<Binding>>,
+    patterns: Vec<(String, Binding)>,
+}
+
+impl BroadcastBindings {
+    pub(crate) fn insert(&mut self, event: &str, binding: Binding) {
+        if is_pattern(event) {
+            self.patterns.push((String::from(event), binding));
+        } else {
+            self.by_event
+                .entry(String::from(event))
+                .or_default()
+                .push(binding);
+        }
+    }
+
+    pub(crate) fn matching<'a>(&'a self, event: &'a str) -> impl Iterator<Item = &'a Binding> {
+        let exact = self.by_event.get(event).into_iter().flatten();
+        let patterns = self
+            .patterns
+            .iter()
+            .filter(move |(pattern, _)| matches_pattern(pattern, event))
+            .map(|(_, binding)| binding);
+
+        exact.chain(patterns)
+    }
+}
+
+fn is_pattern(event: &str) -> bool {
+    event.contains('*')
+}
+
+/// Glob match where `*` stands for any sequence of characters, including an empty one.
+pub(crate) fn matches_pattern(pattern: &str, event: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let event: Vec<char> = event.chars().collect();
+
+    let (mut p, mut e) = (0, 0);
+    // position of the last `*` in the pattern, and of the event character it was matched up to
+    let mut backtrack = None;
+
+    while e < event.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, e));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == event[e] {
+            p += 1;
+            e += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            e = matched + 1;
+            backtrack = Some((star, e));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
@@ -1,26 +1,34 @@
 use std::{
     collections::HashMap,
     mem::{Discriminant, discriminant},
-    sync::Arc,
+    sync::{Arc, Weak},
+    time::Duration,
 };
 
+use bytes::Bytes;
+use serde::{Serialize, de::DeserializeOwned};
 use tokio::sync::{
     Mutex,
     oneshot::{Receiver, Sender, channel},
+    watch,
 };
 
 use crate::{
+    broadcast_bindings::BroadcastBindings,
     channel_event::ChannelEvent,
-    client::RealtimeClient,
+    client::{RealtimeClient, WeakRealtimeClient},
     error::RealtimeError,
+    presence::RealtimePresence,
     protocol_objects::{
-        Broadcast, JoinConfig, Payload, PhxClose, PhxError, PhxJoin, PhxLeave, PhxReply,
-        PhxResponse,
+        BinaryBroadcast, Broadcast, JoinConfig, JoinPostgresChangedEvent, JoinPostgresChanges,
+        Payload, PhxClose, PhxError, PhxJoin, PhxLeave, PhxReply, PhxResponse,
+        PostgresChangesPayload, PresenceDiff, PresencePush, PresenceState, ReplyPostgresChanges,
     },
-    push::Push,
+    push::{Push, PushHandle, PushReply},
+    task::Task,
     types::{
-        Binding, ChannelState, PayloadResponse, PushReplyStatus, Result, SubscribeCallback,
-        SubscribeState,
+        Binding, BroadcastErrorCallback, ChannelState, PayloadResponse, ProtocolVersion,
+        PushReplyStatus, Result, SubscribeCallback, SubscribeState,
     },
     utils::get_reply_event_name,
 };
@@ -30,34 +38,57 @@ pub struct RealtimeChannel {
     topic: String,
     config: JoinConfig,
     join_push: Push,
+    client: WeakRealtimeClient,
+    presence: RealtimePresence,
+    broadcast_error_hooks: Arc<std::sync::RwLock<Vec<BroadcastErrorCallback>>>,
+    state_receiver: watch::Receiver<ChannelState>,
     mutable_state: Arc<Mutex<RealtimeChannelMutableState>>,
 }
 
 #[derive(Default)]
 pub(crate) struct RealtimeChannelMutableState {
-    state: ChannelState,
+    state: watch::Sender<ChannelState>,
     joined_once: bool,
-    push_buffer: Vec<Push>,
+    join_ref: Option<String>,
+    rejoin_timer: Option<Task<()>>,
+    rejoin_attempts: u32,
+    subscribe_callback: Option<Arc<SubscribeCallback>>,
+    push_buffer: Vec<BufferedPush>,
     bindings: HashMap<Discriminant<Payload>, Vec<Binding>>,
+    broadcast_bindings: BroadcastBindings,
     push_senders: HashMap<String, Sender<PayloadResponse>>,
 }
 
+/// A push made while the channel could not send it, sent once the channel is joined.
+struct BufferedPush {
+    push: Push,
+    _ref: String,
+    expiry: Task<()>,
+}
+
 impl RealtimeChannel {
     pub(crate) fn new(client: &RealtimeClient, topic: &str, config: Option<JoinConfig>) -> Self {
         let join_push = Push::new(
             "phx_join",
             Payload::PhxJoin(PhxJoin {
                 config: config.clone().unwrap_or_default(),
-                access_token: String::from(client.get_access_token()),
+                access_token: client.get_access_token(),
             }),
-            None,
+            Some(client.get_timeout()),
+            client.get_logger(),
         );
 
+        let mutable_state = RealtimeChannelMutableState::default();
+
         Self {
             topic: String::from(topic),
             config: config.unwrap_or_default(),
             join_push,
-            mutable_state: Arc::new(Mutex::new(RealtimeChannelMutableState::default())),
+            client: client.downgrade(),
+            presence: RealtimePresence::new(client.get_logger()),
+            broadcast_error_hooks: Arc::default(),
+            state_receiver: mutable_state.state.subscribe(),
+            mutable_state: Arc::new(Mutex::new(mutable_state)),
         }
     }
 
@@ -77,26 +108,64 @@ impl RealtimeChannel {
             ChannelEvent::Reply(Box::new(Self::on_reply)),
         )
         .await;
+
+        for payload in [
+            Payload::PresenceState(PresenceState(HashMap::new())),
+            Payload::PresenceDiff(PresenceDiff {
+                joins: HashMap::new(),
+                leaves: HashMap::new(),
+            }),
+        ] {
+            let presence = self.presence.clone();
+            self.register_event(
+                discriminant(&payload),
+                ChannelEvent::Presence(Box::new(move |state, payload| {
+                    presence.on_payload(payload, state.join_ref.as_deref())
+                })),
+            )
+            .await;
+        }
     }
 
     fn on_close(state: &mut RealtimeChannelMutableState, should_remove_channel: &mut bool) {
-        // reset the rejoin timer
-        state.state = ChannelState::Closed;
+        state.reset_rejoin_timer();
+        if state.state() != ChannelState::Closed {
+            state.set_state(ChannelState::Closed);
+            state.notify_subscriber(Ok(SubscribeState::Closed));
+        }
 
         *should_remove_channel = true;
     }
 
     fn on_error(state: &mut RealtimeChannelMutableState) {
-        if state.state == ChannelState::Closed || state.state == ChannelState::Leaving {
+        if state.state() == ChannelState::Closed || state.state() == ChannelState::Leaving {
             return;
         }
 
-        state.state = ChannelState::Errored;
-        // rejoin
+        state.set_state(ChannelState::Errored);
+        state.notify_subscriber(Ok(SubscribeState::ChannelError));
+        // rejoined by the rejoin timer, see `trigger`
     }
 
     fn on_reply(state: &mut RealtimeChannelMutableState, payload: Payload, _ref: Option<&str>) {
         if let Payload::PhxReply(ref reply) = payload {
+            if _ref.is_some() && _ref == state.join_ref.as_deref() {
+                match reply {
+                    PhxReply::Ok(response) => {
+                        state.reset_rejoin_timer();
+                        match state.bind_postgres_changes(&response.postgres_changes) {
+                            Ok(()) => state.set_state(ChannelState::Joined),
+                            Err(error) => {
+                                // left by `trigger`, rejoining would fail the same way
+                                state.set_state(ChannelState::Leaving);
+                                state.notify_subscriber(Err(error));
+                            }
+                        }
+                    }
+                    PhxReply::Error(_) => state.set_state(ChannelState::Errored),
+                }
+            }
+
             match reply {
                 PhxReply::Ok(_) => {
                     if let Some(_ref) = _ref {
@@ -126,53 +195,57 @@ impl RealtimeChannel {
         }
     }
 
-    pub async fn subscribe(
-        &mut self,
-        client: &mut RealtimeClient,
-        callback: Option<SubscribeCallback>,
-    ) -> Result<()> {
+    pub async fn subscribe(&mut self, callback: Option<SubscribeCallback>) -> Result<()> {
+        let client = self.client()?;
         if !client.is_connected() {
             client.connect().await?;
         }
+        // the provided token may have expired since connecting
+        client.ensure_access_token().await?;
 
-        if self.mutable_state.lock().await.joined_once {
-            return Err(RealtimeError::MultipleSubscriptionError);
-        }
+        let callback = callback.map(Arc::new);
+
+        {
+            let mut mutable_state = self.mutable_state.lock().await;
+            // a channel can be subscribed again once it was left or closed by the server
+            if mutable_state.joined_once && mutable_state.state() != ChannelState::Closed {
+                return Err(RealtimeError::MultipleSubscriptionError);
+            }
 
-        // let mut config_payload = self.config.clone();
+            // set along with `joined_once` so that concurrent calls see the channel is taken
+            mutable_state.set_state(ChannelState::Joining);
+            mutable_state.joined_once = true;
+            mutable_state.rejoin_attempts = 0;
+            mutable_state.subscribe_callback = callback.clone();
+        }
 
-        // let postgres_changes: Vec<_> = self
-        //     .events
-        //     .iter()
-        //     .filter_map(|(event_type, _)| {
-        //         if event_type == "postgres_changes" {
-        //             // Extract filter from the event binding
-        //             // This would need to be implemented based on your event structure
-        //             None // Placeholder for now
-        //         } else {
-        //             None
-        //         }
-        //     })
-        //     .collect();
+        // the channel was removed from the client when it was closed
+        client.add_channel(self).await;
 
-        // update payload with the config
-        self.mutable_state.lock().await.joined_once = true;
+        // hooks and reply of the previous subscription
+        self.join_push.clear_event_callbacks().await;
+        self.join_push.reset();
 
-        let callback = Arc::new(callback);
         let on_join_push_ok = {
-            let callback = Arc::clone(&callback);
+            let callback = callback.clone();
+            let state_receiver = self.state_receiver.clone();
             move |_: &Payload| {
-                if let Some(ref callback) = *callback {
+                // the reply may have been rejected, see `on_reply`
+                if *state_receiver.borrow() != ChannelState::Joined {
+                    return;
+                }
+
+                if let Some(ref callback) = callback {
                     callback(Ok(SubscribeState::Subscribed));
                 }
             }
         };
 
         let on_join_push_error = {
-            let callback = Arc::clone(&callback);
+            let callback = callback.clone();
             move |payload: &Payload| {
                 println!("Calling 'on_join_push_error' PushEvent.");
-                if let Some(ref callback) = *callback {
+                if let Some(ref callback) = callback {
                     let payload_raw = serde_json::to_string(payload).unwrap_or(String::from(
                         "Subscription error payload failed to deserialize.",
                     ));
@@ -183,10 +256,25 @@ impl RealtimeChannel {
             }
         };
 
-        let on_join_push_timeout = move |_: &Payload| {
-            println!("Calling 'on_join_push_timeout' PushEvent.");
-            if let Some(ref callback) = *callback {
-                callback(Ok(SubscribeState::TimedOut));
+        let on_join_push_timeout = {
+            // the hooks are stored by the channel, holding it here would keep it alive forever
+            let client = self.client.clone();
+            let topic = self.topic.clone();
+            let mutable_state = Arc::downgrade(&self.mutable_state);
+            move |_: &Payload| {
+                println!("Calling 'on_join_push_timeout' PushEvent.");
+                if let Some(ref callback) = callback {
+                    callback(Ok(SubscribeState::TimedOut));
+                }
+
+                let client = client.clone();
+                let topic = topic.clone();
+                let mutable_state = mutable_state.clone();
+                tokio::spawn(async move {
+                    if let Some(channel) = Self::find(&client, &topic, &mutable_state).await {
+                        channel.on_join_timeout().await;
+                    }
+                });
             }
         };
 
@@ -200,6 +288,152 @@ impl RealtimeChannel {
             .register_receive_callback(PushReplyStatus::TimedOut, Box::new(on_join_push_timeout))
             .await;
 
+        self.send_join(&client).await
+    }
+
+    /// Sends `phx_join` again after the socket was re-established, if this channel was joined or
+    /// joining when the previous connection was lost.
+    pub(crate) async fn rejoin_after_reconnect(&mut self, client: &RealtimeClient) -> Result<()> {
+        {
+            let mutable_state = self.mutable_state.lock().await;
+            if !mutable_state.joined_once
+                || matches!(
+                    mutable_state.state(),
+                    ChannelState::Closed | ChannelState::Leaving
+                )
+            {
+                return Ok(());
+            }
+        }
+
+        self.send_join(client).await
+    }
+
+    /// The channel of `client` owning `mutable_state`, unless it was dropped or removed.
+    async fn find(
+        client: &WeakRealtimeClient,
+        topic: &str,
+        mutable_state: &Weak<Mutex<RealtimeChannelMutableState>>,
+    ) -> Option<RealtimeChannel> {
+        let mutable_state = mutable_state.upgrade()?;
+        let channel = client.upgrade()?.get_channel(topic).await?;
+        Arc::ptr_eq(&channel.mutable_state, &mutable_state).then_some(channel)
+    }
+
+    async fn on_join_timeout(&self) {
+        let Some(client) = self.client.upgrade() else {
+            return;
+        };
+
+        let mut mutable_state = self.mutable_state.lock().await;
+        if mutable_state.state() != ChannelState::Joining {
+            return;
+        }
+
+        mutable_state.set_state(ChannelState::Errored);
+        self.schedule_rejoin(&mut mutable_state, &client);
+    }
+
+    /// Sends `phx_join` again after a backoff decided by the client's rejoin policy, unless the
+    /// channel was joined or left in the meantime. Closes the channel once the policy gives up.
+    fn schedule_rejoin(&self, state: &mut RealtimeChannelMutableState, client: &RealtimeClient) {
+        if state.rejoin_timer.is_some() {
+            return;
+        }
+
+        state.rejoin_attempts += 1;
+        let attempt = state.rejoin_attempts;
+        let Some(delay) = client.get_rejoin_policy().next_delay(attempt) else {
+            client.log(
+                "channel",
+                &format!(
+                    "Giving up rejoining {} after {attempt} attempts.",
+                    self.topic
+                ),
+            );
+            // closed so that it can be subscribed again
+            state.rejoin_attempts = 0;
+            state.set_state(ChannelState::Closed);
+            state.notify_subscriber(Ok(SubscribeState::Closed));
+            return;
+        };
+
+        let mut channel = self.clone();
+        state.rejoin_timer = Some(Task::spawn(async move {
+            tokio::time::sleep(delay).await;
+            channel.rejoin_from_timer(attempt).await;
+        }));
+    }
+
+    async fn rejoin_from_timer(&mut self, attempt: u32) {
+        let Some(client) = self.client.upgrade() else {
+            return;
+        };
+
+        {
+            let mut mutable_state = self.mutable_state.lock().await;
+            mutable_state.rejoin_timer = None;
+            if mutable_state.state() != ChannelState::Errored {
+                return;
+            }
+
+            mutable_state.notify_subscriber(Ok(SubscribeState::Rejoining { attempt }));
+        }
+
+        // the channel is rejoined by the client once the connection is re-established
+        if !client.is_connected() {
+            return;
+        }
+
+        if let Err(error) = self.send_join(&client).await {
+            client.log(
+                "channel",
+                &format!("Failed to rejoin {}: {error}", self.topic),
+            );
+        }
+    }
+
+    pub(crate) async fn on_connection_lost(&self) {
+        let mut mutable_state = self.mutable_state.lock().await;
+        mutable_state.reset_rejoin_timer();
+        if matches!(
+            mutable_state.state(),
+            ChannelState::Joined | ChannelState::Joining
+        ) {
+            mutable_state.set_state(ChannelState::Errored);
+        }
+
+        // the join is sent again once reconnected, it didn't time out
+        self.join_push.cancel_timeout();
+        // replies to pushes sent on the lost connection will never arrive
+        mutable_state.push_senders.clear();
+    }
+
+    /// Closes the channel once there is no connection to rejoin it on, so that it can be
+    /// subscribed again.
+    pub(crate) async fn on_connection_closed(&self) {
+        let buffered_pushes = {
+            let mut mutable_state = self.mutable_state.lock().await;
+            mutable_state.reset_rejoin_timer();
+            // the subscriber is told the channel closed, not that the join timed out
+            self.join_push.cancel_timeout();
+            mutable_state.push_senders.clear();
+            if mutable_state.state() != ChannelState::Closed {
+                mutable_state.set_state(ChannelState::Closed);
+                mutable_state.notify_subscriber(Ok(SubscribeState::Closed));
+            }
+
+            std::mem::take(&mut mutable_state.push_buffer)
+        };
+
+        // they would only be sent if the channel was subscribed again
+        for buffered in buffered_pushes {
+            buffered.expiry.cancel();
+            buffered.push.expire().await;
+        }
+    }
+
+    async fn send_join(&mut self, client: &RealtimeClient) -> Result<()> {
         let (sender, receiver) = channel();
         let _ref = client.make_ref().await;
         let reply_event_name = get_reply_event_name(&_ref);
@@ -209,44 +443,109 @@ impl RealtimeChannel {
             mutable_state.push_senders.insert(reply_event_name, sender);
         }
 
-        self.rejoin(client, &_ref, receiver).await?;
-
-        Ok(())
+        self.rejoin(client, &_ref, receiver).await
     }
 
-    pub(crate) async fn unsubscribe(&self, client: &RealtimeClient) {
+    /// Leaves the channel, resolving with the server's reply to `phx_leave`. `phx_leave` is only
+    /// sent if the channel is joined or being joined and the client is connected, otherwise this
+    /// resolves with `None`. Does nothing if the channel is already closed. The channel keeps its
+    /// bindings and can be subscribed again.
+    pub async fn unsubscribe(&self) -> Result<Option<PushReply>> {
+        let client = self.client()?;
+        // taken before locking the channel, `trigger` runs with the client's state locked
+        let _ref = client.make_ref().await;
+
+        let mut leave_push = Push::new(
+            "phx_leave",
+            Payload::PhxLeave(PhxLeave),
+            Some(client.get_timeout()),
+            client.get_logger(),
+        );
+        let handle = PushHandle::new(leave_push.wait_for_reply().await);
+
+        let sent = {
+            let mut state = self.mutable_state.lock().await;
+            if state.state() == ChannelState::Closed {
+                return Ok(None);
+            }
+
+            let was_joined = matches!(
+                state.state(),
+                ChannelState::Joined | ChannelState::Joining | ChannelState::Leaving
+            );
+            state.set_state(ChannelState::Leaving);
+            state.reset_rejoin_timer();
+
+            if was_joined && client.is_connected() {
+                let (sender, receiver) = channel();
+                let reply_event_name = get_reply_event_name(&_ref);
+                state.push_senders.insert(reply_event_name.clone(), sender);
+
+                let join_ref = state.join_ref.clone();
+                Some(
+                    leave_push
+                        .send(
+                            &client,
+                            &self.topic,
+                            join_ref.as_deref(),
+                            &_ref,
+                            &reply_event_name,
+                            receiver,
+                        )
+                        .await,
+                )
+            } else {
+                None
+            }
+        };
+
+        let reply = match sent {
+            Some(Ok(())) => Ok(Some(handle.await)),
+            Some(Err(error)) => Err(error),
+            // the server doesn't know about the channel
+            None => Ok(None),
+        };
+
         let mut state = self.mutable_state.lock().await;
-        state.state = ChannelState::Leaving;
+        state.reset_rejoin_timer();
+        if state.state() != ChannelState::Closed {
+            state.set_state(ChannelState::Closed);
+            state.notify_subscriber(Ok(SubscribeState::Closed));
+        }
 
-        // reset rejoin timer
+        reply
+    }
 
-        let payload = Payload::PhxLeave(PhxLeave);
-        let mut leave_push = Push::new("phx_leave", payload, None);
-        let topic_clone = self.topic.clone();
-        leave_push
-            .register_receive_callback(
-                PushReplyStatus::Ok,
-                Box::new(move |_| println!("Leaving channel {topic_clone}")),
-            )
-            .await;
+    /// Drops every binding and buffered push, keeping only the ones the channel itself needs.
+    pub(crate) async fn clear_bindings(&self) {
+        {
+            let mut state = self.mutable_state.lock().await;
+            state.bindings.clear();
+            state.broadcast_bindings = BroadcastBindings::default();
+            for buffered in state.push_buffer.drain(..) {
+                buffered.expiry.cancel();
+            }
+        }
 
-        let (sender, receiver) = channel();
-        let _ref = client.make_ref().await;
-        let reply_event_name = get_reply_event_name(&_ref);
-        state.push_senders.insert(reply_event_name, sender);
+        self.register_default_events().await;
+    }
 
-        leave_push
-            .send(client, &self.topic, &_ref, "phx_leave", receiver)
-            .await;
+    /// Pushes `payload` to the channel under `event`. The returned handle resolves with the
+    /// server's reply, or with [`PushReply::TimedOut`] after the client's timeout.
+    pub async fn send(&self, event: &str, payload: Payload) -> Result<PushHandle> {
+        let timeout = self.client()?.get_timeout();
+        self.send_with_timeout(event, payload, timeout).await
     }
 
-    async fn push(
+    /// Same as [`send`](Self::send), waiting `timeout` for the reply instead of the client's
+    /// timeout.
+    pub async fn send_with_timeout(
         &self,
-        client: &RealtimeClient,
         event: &str,
         payload: Payload,
-        // timeout
-    ) -> Result<()> {
+        timeout: Duration,
+    ) -> Result<PushHandle> {
+        let client = self.client()?;
         if !self.mutable_state.lock().await.joined_once {
             return Err(RealtimeError::PushWhileUnsubscribedError {
                 event: String::from(event),
@@ -254,32 +553,140 @@ impl RealtimeChannel {
             });
         }
 
-        let mut push = Push::new(event, payload, None);
+        let mut push = Push::new(event, payload, Some(timeout), client.get_logger());
 
-        let (sender, receiver) = channel();
         let _ref = client.make_ref().await;
         let reply_event_name = get_reply_event_name(&_ref);
 
+        // the reply will never be routed once the push timed out
+        let on_timeout = {
+            let mutable_state = self.mutable_state.clone();
+            let reply_event_name = reply_event_name.clone();
+            move |_: &Payload| {
+                let mutable_state = mutable_state.clone();
+                let reply_event_name = reply_event_name.clone();
+                tokio::spawn(async move {
+                    mutable_state
+                        .lock()
+                        .await
+                        .push_senders
+                        .remove(&reply_event_name);
+                });
+            }
+        };
+        push.register_receive_callback(PushReplyStatus::TimedOut, Box::new(on_timeout))
+            .await;
+
+        let handle = PushHandle::new(push.wait_for_reply().await);
+
+        let mut mutable_state = self.mutable_state.lock().await;
+        // pushes made while earlier ones are still buffered wait for them, to keep their order
+        if client.is_connected()
+            && mutable_state.state() == ChannelState::Joined
+            && mutable_state.push_buffer.is_empty()
         {
-            let mut mutable_state = self.mutable_state.lock().await;
-            mutable_state
-                .push_senders
-                .insert(reply_event_name.clone(), sender);
+            self.send_push(&mut mutable_state, &client, &mut push, &_ref)
+                .await?;
+        } else {
+            self.buffer_push(&mut mutable_state, &client, event, push, _ref)?;
         }
 
-        if self.can_push(client).await {
-            return push
-                .send(client, &self.topic, &_ref, &reply_event_name, receiver)
-                .await;
-        } else {
-            println!("Didn't push event {reply_event_name} because client was not connected.");
-            // push.start_timeout(self, client, receiver);
-            // self.push_buffer.push(push);
+        Ok(handle)
+    }
+
+    async fn send_push(
+        &self,
+        state: &mut RealtimeChannelMutableState,
+        client: &RealtimeClient,
+        push: &mut Push,
+        _ref: &str,
+    ) -> Result<()> {
+        let (sender, receiver) = channel();
+        let reply_event_name = get_reply_event_name(_ref);
+        state.push_senders.insert(reply_event_name.clone(), sender);
+
+        push.send(
+            client,
+            &self.topic,
+            state.join_ref.as_deref(),
+            _ref,
+            &reply_event_name,
+            receiver,
+        )
+        .await
+    }
+
+    /// Keeps `push` until the channel is joined. It times out if it is still buffered once the
+    /// client's push buffer TTL has elapsed.
+    fn buffer_push(
+        &self,
+        state: &mut RealtimeChannelMutableState,
+        client: &RealtimeClient,
+        event: &str,
+        push: Push,
+        _ref: String,
+    ) -> Result<()> {
+        let capacity = client.get_push_buffer_capacity();
+        if state.push_buffer.len() >= capacity {
+            return Err(RealtimeError::PushBufferFull {
+                event: String::from(event),
+                topic: self.topic.clone(),
+                capacity,
+            });
         }
 
+        let expiry = {
+            let mutable_state = self.mutable_state.clone();
+            let ttl = client.get_push_buffer_ttl();
+            let _ref = _ref.clone();
+            Task::spawn(async move {
+                tokio::time::sleep(ttl).await;
+
+                let mut state = mutable_state.lock().await;
+                let Some(position) = state.push_buffer.iter().position(|b| b._ref == _ref) else {
+                    return;
+                };
+                let expired = state.push_buffer.remove(position);
+                drop(state);
+
+                expired.push.expire().await;
+            })
+        };
+
+        state.push_buffer.push(BufferedPush { push, _ref, expiry });
         Ok(())
     }
 
+    /// Sends the pushes buffered while the channel was not joined, in the order they were made.
+    async fn flush_push_buffer(&self) {
+        let Some(client) = self.client.upgrade() else {
+            return;
+        };
+
+        let mut mutable_state = self.mutable_state.lock().await;
+        if !client.is_connected() || mutable_state.state() != ChannelState::Joined {
+            return;
+        }
+
+        for mut buffered in std::mem::take(&mut mutable_state.push_buffer) {
+            buffered.expiry.cancel();
+            let result = self
+                .send_push(
+                    &mut mutable_state,
+                    &client,
+                    &mut buffered.push,
+                    &buffered._ref,
+                )
+                .await;
+            if let Err(error) = result {
+                client.log(
+                    "channel",
+                    &format!("Failed to send buffered push to {}: {error}", self.topic),
+                );
+            }
+        }
+    }
+
     pub(crate) async fn register_event(
         &self,
         discriminant: Discriminant<Payload>,
@@ -295,24 +702,181 @@ impl RealtimeChannel {
             .push(binding);
     }
 
+    /// Listens to broadcasts of `event`, which may also be `*` for every event or a pattern like
+    /// `cursor.*`.
     pub async fn on_broadcast<F>(&self, event: &str, f: F)
     where
         F: Fn(Payload) + Send + Sync + 'static,
     {
+        let binding = Binding::new(ChannelEvent::Broadcast(Box::new(f)), None);
+        self.mutable_state
+            .lock()
+            .await
+            .broadcast_bindings
+            .insert(event, binding);
+    }
+
+    /// Listens to broadcasts of `event` (see [`on_broadcast`](Self::on_broadcast)), deserializing
+    /// their payload as `T`. Payloads that fail to deserialize are reported to the
+    /// [`on_broadcast_error`](Self::on_broadcast_error) hooks.
+    pub async fn on_broadcast_typed<T, F>(&self, event: &str, f: F)
+    where
+        T: DeserializeOwned,
+        F: Fn(T) + Send + Sync + 'static,
+    {
+        let error_hooks = self.broadcast_error_hooks.clone();
+        let client = self.client.clone();
+        self.on_broadcast(event, move |payload| {
+            let (event, result) = match payload {
+                Payload::Broadcast(broadcast) => {
+                    (broadcast.event, serde_json::from_value(broadcast.payload))
+                }
+                Payload::BinaryBroadcast(broadcast) => {
+                    (broadcast.event, serde_json::from_slice(&broadcast.payload))
+                }
+                _ => return,
+            };
+
+            match result {
+                Ok(value) => f(value),
+                Err(source) => {
+                    let error = RealtimeError::BroadcastDecodeError { event, source };
+                    let error_hooks = error_hooks.read().unwrap();
+                    if error_hooks.is_empty()
+                        && let Some(client) = client.upgrade()
+                    {
+                        client.log("channel", &error.to_string());
+                    }
+                    for hook in error_hooks.iter() {
+                        hook(&error);
+                    }
+                }
+            }
+        })
+        .await
+    }
+
+    /// Called when a broadcast received by an [`on_broadcast_typed`](Self::on_broadcast_typed)
+    /// handler can't be deserialized. Without hooks, the error is logged.
+    pub fn on_broadcast_error<F>(&self, f: F)
+    where
+        F: Fn(&RealtimeError) + Send + Sync + 'static,
+    {
+        self.broadcast_error_hooks
+            .write()
+            .unwrap()
+            .push(Arc::new(f));
+    }
+
+    /// Listens to database changes matching `event`, `schema`, `table` and the optional `filter`
+    /// (e.g. `id=eq.1`). Must be called before `subscribe`, as the filters are sent when joining.
+    pub async fn on_postgres_changes<F>(
+        &self,
+        event: JoinPostgresChangedEvent,
+        schema: &str,
+        table: &str,
+        filter: Option<&str>,
+        f: F,
+    ) where
+        F: Fn(PostgresChangesPayload) + Send + Sync + 'static,
+    {
+        let filter = JoinPostgresChanges {
+            event,
+            schema: String::from(schema),
+            table: String::from(table),
+            filter: filter.map(String::from),
+        };
+
         self.register_event(
-            discriminant(&Payload::Broadcast(Broadcast::default())),
-            ChannelEvent::Broadcast(Box::new(f)),
+            postgres_changes_discriminant(),
+            ChannelEvent::PostgresChanges(filter, Box::new(f)),
         )
         .await
     }
 
-    pub async fn send_broadcast(
-        &self,
-        client: &RealtimeClient,
-        event: &str,
-        payload: Payload,
-    ) -> Result<()> {
-        self.push(client, event, payload).await
+    /// Sends a broadcast. If the channel was joined with `ack` enabled in its broadcast config,
+    /// waits until the server acknowledges it.
+    pub async fn send_broadcast(&self, event: &str, payload: Payload) -> Result<()> {
+        let acknowledged = self
+            .config
+            .broadcast
+            .as_ref()
+            .is_some_and(|broadcast| broadcast.ack);
+        let reply = self.send(event, payload).await?;
+        if !acknowledged {
+            return Ok(());
+        }
+
+        match reply.await {
+            PushReply::Ok(_) => Ok(()),
+            PushReply::Error(reason) => Err(RealtimeError::BroadcastError {
+                event: String::from(event),
+                reason,
+            }),
+            PushReply::TimedOut => Err(RealtimeError::PushTimeout {
+                event: String::from(event),
+            }),
+        }
+    }
+
+    /// Broadcasts `value` as the payload of `event`.
+    pub async fn broadcast<T: Serialize>(&self, event: &str, value: &T) -> Result<()> {
+        let payload = Payload::Broadcast(Broadcast {
+            event: String::from(event),
+            payload: serde_json::to_value(value)?,
+        });
+
+        self.send_broadcast(event, payload).await
+    }
+
+    /// Broadcasts `payload` as raw bytes in a binary frame. Requires the client to use
+    /// [`ProtocolVersion::V2`](crate::types::ProtocolVersion::V2).
+    pub async fn broadcast_binary(&self, event: &str, payload: impl Into<Bytes>) -> Result<()> {
+        let client = self.client.upgrade().ok_or(RealtimeError::ClientDropped)?;
+        if client.get_vsn() == ProtocolVersion::V1 {
+            return Err(RealtimeError::BinaryBroadcastUnsupported);
+        }
+        // the frame header stores its length in a single byte
+        if event.len() > u8::MAX as usize {
+            return Err(RealtimeError::BinaryFrameError {
+                reason: format!("event '{event}' is longer than 255 bytes"),
+            });
+        }
+
+        let payload = Payload::BinaryBroadcast(BinaryBroadcast {
+            event: String::from(event),
+            payload: payload.into(),
+        });
+
+        self.send_broadcast(event, payload).await
+    }
+
+    /// Starts tracking this client's presence on the channel, or updates its metadata if it is
+    /// already tracked.
+    pub async fn track<T: Serialize>(&self, meta: &T) -> Result<PushHandle> {
+        let meta = serde_json::to_value(meta)?;
+        self.send("presence", Payload::Presence(PresencePush::track(meta)))
+            .await
+    }
+
+    /// Stops tracking this client's presence on the channel.
+    pub async fn untrack(&self) -> Result<PushHandle> {
+        self.send("presence", Payload::Presence(PresencePush::untrack()))
+            .await
+    }
+
+    /// Presence state of the channel, kept in sync with the server once subscribed.
+    pub fn presence(&self) -> &RealtimePresence {
+        &self.presence
+    }
+
+    /// Receives every transition of the channel's state, e.g. `Joining` -> `Joined`.
+    pub fn state_watch(&self) -> watch::Receiver<ChannelState> {
+        self.state_receiver.clone()
+    }
+
+    fn client(&self) -> Result<RealtimeClient> {
+        self.client.upgrade().ok_or(RealtimeError::ClientDropped)
     }
 
     pub fn get_topic(&self) -> &str {
@@ -321,21 +885,26 @@ impl RealtimeChannel {
 
     async fn rejoin(
         &mut self,
-        client: &mut RealtimeClient,
+        client: &RealtimeClient,
         _ref: &str,
         receiver: Receiver<PayloadResponse>,
     ) -> Result<()> {
         let mut mutable_state = self.mutable_state.lock().await;
-        if mutable_state.state == ChannelState::Leaving {
+        if mutable_state.state() == ChannelState::Leaving {
             return Ok(());
         }
 
         // TODO: client leave open topic
-        mutable_state.state = ChannelState::Joining;
+        mutable_state.cancel_rejoin_timer();
+        mutable_state.set_state(ChannelState::Joining);
+        mutable_state.join_ref = Some(String::from(_ref));
+        self.join_push
+            .update_payload(self.join_payload(&mutable_state, client));
         self.join_push
             .resend(
                 client,
                 &self.topic,
+                Some(_ref),
                 _ref,
                 &get_reply_event_name(_ref),
                 receiver,
@@ -343,13 +912,37 @@ impl RealtimeChannel {
             .await
     }
 
-    async fn can_push(&self, client: &RealtimeClient) -> bool {
-        client.is_connected() && self.mutable_state.lock().await.joined_once
+    /// Builds the `phx_join` payload from the channel's config, adding a postgres changes filter
+    /// for every `on_postgres_changes` binding.
+    fn join_payload(
+        &self,
+        state: &RealtimeChannelMutableState,
+        client: &RealtimeClient,
+    ) -> Payload {
+        let mut config = self.config.clone();
+
+        let postgres_changes: Vec<_> = state
+            .bindings
+            .get(&postgres_changes_discriminant())
+            .into_iter()
+            .flatten()
+            .filter_map(|binding| binding.postgres_changes_filter().cloned())
+            .collect();
+        if !postgres_changes.is_empty() {
+            config.postgres_changes = Some(postgres_changes);
+        }
+
+        Payload::PhxJoin(PhxJoin {
+            config,
+            access_token: client.get_access_token(),
+        })
     }
 
     pub(crate) async fn trigger(
         &self,
+        client: &RealtimeClient,
         payload: Payload,
+        join_ref: Option<&str>,
         _ref: Option<&str>,
         should_remove_channel: &mut bool,
     ) {
@@ -360,19 +953,50 @@ impl RealtimeChannel {
             discriminant(&Payload::PhxJoin(PhxJoin::default())),
         ];
 
-        if let Some(_ref) = _ref
-            && let Some(join_push_ref) = self.join_push.get_ref()
+        let mut mutable_state = self.mutable_state.lock().await;
+
+        if let Some(join_ref) = join_ref {
+            // V2 messages tell which join they belong to
+            if Some(join_ref) != mutable_state.join_ref.as_deref() {
+                client.log(
+                    "channel",
+                    &format!("Ignoring {payload} payload from outdated join {join_ref}."),
+                );
+                return;
+            }
+        } else if let Some(_ref) = _ref
+            && let Some(join_ref) = mutable_state.join_ref.as_deref()
             && maybe_ignore.contains(&discriminant(&payload))
-            && _ref != join_push_ref
+            && _ref != join_ref
         {
-            println!("Ignoring trigger for {payload} payload with ref {_ref}.");
+            // without it, only lifecycle events replying to another push than the join are
+            // known to be outdated
+            client.log(
+                "channel",
+                &format!("Ignoring trigger for {payload} payload with ref {_ref}."),
+            );
             return;
         }
 
-        let mut mutable_state = self.mutable_state.lock().await;
+        let previous_state = mutable_state.state();
+
+        if let Payload::Broadcast(Broadcast { event, .. })
+        | Payload::BinaryBroadcast(BinaryBroadcast { event, .. }) = &payload
+        {
+            let broadcast_bindings = std::mem::take(&mut mutable_state.broadcast_bindings);
+            for binding in broadcast_bindings.matching(event) {
+                binding.invoke(
+                    &mut mutable_state,
+                    payload.clone(),
+                    _ref,
+                    should_remove_channel,
+                );
+            }
+            mutable_state.broadcast_bindings = broadcast_bindings;
+        }
 
         if let Some(bindings) = mutable_state.bindings.remove(&discriminant(&payload)) {
-            for binding in &bindings {
+            for binding in bindings.iter().filter(|binding| binding.accepts(&payload)) {
                 binding.invoke(
                     &mut mutable_state,
                     payload.clone(),
@@ -384,5 +1008,96 @@ impl RealtimeChannel {
                 .bindings
                 .insert(discriminant(&payload), bindings);
         }
+
+        if previous_state != ChannelState::Errored && mutable_state.state() == ChannelState::Errored
+        {
+            self.schedule_rejoin(&mut mutable_state, client);
+        }
+
+        if previous_state != ChannelState::Leaving && mutable_state.state() == ChannelState::Leaving
+        {
+            let channel = self.clone();
+            let client = client.clone();
+            tokio::spawn(async move {
+                if let Err(error) = channel.unsubscribe().await {
+                    client.log(
+                        "channel",
+                        &format!("Failed to leave {}: {error}", channel.topic),
+                    );
+                }
+            });
+        }
+
+        if previous_state != ChannelState::Joined && mutable_state.state() == ChannelState::Joined {
+            let channel = self.clone();
+            tokio::spawn(async move { channel.flush_push_buffer().await });
+        }
+    }
+}
+
+impl RealtimeChannelMutableState {
+    fn state(&self) -> ChannelState {
+        *self.state.borrow()
+    }
+
+    /// Publishes `new_state`, notifying watchers only if it differs from the current state.
+    fn set_state(&mut self, new_state: ChannelState) {
+        self.state.send_if_modified(|state| {
+            let modified = *state != new_state;
+            *state = new_state;
+            modified
+        });
+    }
+
+    fn cancel_rejoin_timer(&mut self) {
+        if let Some(rejoin_timer) = self.rejoin_timer.take() {
+            rejoin_timer.cancel();
+        }
+    }
+
+    fn reset_rejoin_timer(&mut self) {
+        self.cancel_rejoin_timer();
+        self.rejoin_attempts = 0;
     }
+
+    fn notify_subscriber(&self, state: Result<SubscribeState>) {
+        if let Some(callback) = &self.subscribe_callback {
+            callback(state);
+        }
+    }
+
+    /// Assigns the ids the server gave to the postgres changes filters sent in `phx_join`, in the
+    /// same order, to their bindings.
+    fn bind_postgres_changes(&mut self, server_filters: &[ReplyPostgresChanges]) -> Result<()> {
+        let Some(bindings) = self.bindings.get_mut(&postgres_changes_discriminant()) else {
+            return Ok(());
+        };
+
+        for (index, binding) in bindings.iter_mut().enumerate() {
+            let Some(filter) = binding.postgres_changes_filter() else {
+                continue;
+            };
+
+            match server_filters.get(index) {
+                Some(server_filter) if server_filter.matches(filter) => {
+                    binding.set_id(i64::from(server_filter.id));
+                }
+                server_filter => {
+                    return Err(RealtimeError::PostgresChangesMismatch {
+                        requested: serde_json::to_string(filter)?,
+                        returned: match server_filter {
+                            Some(server_filter) => serde_json::to_string(server_filter)?,
+                            None => String::from("nothing"),
+                        },
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn postgres_changes_discriminant() -> Discriminant<Payload> {
+    discriminant(&Payload::PostgresChanges(PostgresChangesPayload::default()))
 }
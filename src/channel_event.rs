@@ -1,10 +1,20 @@
-use crate::{channel::RealtimeChannelMutableState, protocol_objects::Payload};
+use crate::{
+    channel::RealtimeChannelMutableState,
+    protocol_objects::{JoinPostgresChanges, Payload, PostgresChangesPayload},
+};
+
+type PresenceEventCallback = Box<dyn Fn(&RealtimeChannelMutableState, Payload) + Send + Sync>;
 
 pub(crate) enum ChannelEvent {
     Reply(Box<dyn Fn(&mut RealtimeChannelMutableState, Payload, Option<&str>) + Send + Sync>),
     Broadcast(Box<dyn Fn(Payload) + Send + Sync>),
     Error(Box<dyn Fn(&mut RealtimeChannelMutableState) + Send + Sync>),
     Close(Box<dyn Fn(&mut RealtimeChannelMutableState, &mut bool) + Send + Sync>),
+    Presence(PresenceEventCallback),
+    PostgresChanges(
+        JoinPostgresChanges,
+        Box<dyn Fn(PostgresChangesPayload) + Send + Sync>,
+    ),
 }
 
 impl ChannelEvent {
@@ -20,6 +30,20 @@ impl ChannelEvent {
             ChannelEvent::Broadcast(event) => event(payload),
             ChannelEvent::Error(event) => event(channel_state),
             ChannelEvent::Close(event) => event(channel_state, should_remove_channel),
+            ChannelEvent::Presence(event) => event(channel_state, payload),
+            ChannelEvent::PostgresChanges(_, event) => {
+                if let Payload::PostgresChanges(payload) = payload {
+                    event(payload)
+                }
+            }
+        }
+    }
+
+    /// Filter sent in `phx_join` for a postgres changes binding.
+    pub(crate) fn postgres_changes_filter(&self) -> Option<&JoinPostgresChanges> {
+        match self {
+            ChannelEvent::PostgresChanges(filter, _) => Some(filter),
+            _ => None,
         }
     }
 }
@@ -1,32 +1,88 @@
-use std::{collections::HashMap, pin::Pin, sync::Arc};
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::{Arc, RwLock, Weak},
+    time::{Duration, SystemTime},
+};
 
-use tokio::sync::Mutex;
+use futures::future::join_all;
+use tokio::{
+    sync::{Mutex, broadcast, watch},
+    time::interval,
+};
+use tokio_tungstenite::tungstenite::{
+    client::IntoClientRequest,
+    http::{HeaderMap, Request},
+};
 
 use crate::{
     channel::RealtimeChannel,
-    connection::RealtimeConnection,
+    client_builder::{RealtimeClientBuilder, invalid_option},
+    connection::{RealtimeConnection, set_connection_state},
     error::RealtimeError,
-    protocol_objects::{JoinConfig, Message},
-    types::Result,
-    utils::{http_to_ws, is_ws_url},
+    latency::HeartbeatLatency,
+    protocol_objects::{AccessToken, JoinConfig, Message, Payload},
+    push::PushReply,
+    reconnect_policy::{ExponentialBackoff, NeverRetry, ReconnectPolicy},
+    task::Task,
+    token_provider::{TokenProvider, expires_at},
+    types::{
+        ChannelState, ConnectionState, DEFAULT_INITIAL_BACKOFF, DEFAULT_MAX_BACKOFF,
+        DEFAULT_MAX_RETRIES, DecodeErrorCallback, Logger, ProtocolVersion, Result,
+        TOKEN_RETRY_DELAY,
+    },
+    utils::http_to_ws,
 };
 
-// #[derive(Clone)]
+/// Handle to a realtime client. Cloning it is cheap, and every clone shares the same connection
+/// and channels. The connection is closed once the last handle is dropped.
+#[derive(Clone)]
 pub struct RealtimeClient {
+    inner: Arc<RealtimeClientInner>,
+}
+
+/// Handle that doesn't keep the client alive, held by channels and background tasks.
+#[derive(Clone)]
+pub(crate) struct WeakRealtimeClient {
+    inner: Weak<RealtimeClientInner>,
+}
+
+struct RealtimeClientInner {
     url: String,
     api_key: String,
-    access_token: String,
-    auto_reconnect: bool,
-    max_retries: u32,
-    initial_backoff: f32,
-    connection: Option<RealtimeConnection>,
-    mutable_state: Arc<Mutex<RealtimeClientMutableState>>,
+    access_token: RwLock<String>,
+    options: ClientOptions,
+    connection: RwLock<Option<RealtimeConnection>>,
+    connection_state: watch::Sender<ConnectionState>,
+    latency: Arc<HeartbeatLatency>,
+    decode_error_hooks: RwLock<Vec<DecodeErrorCallback>>,
+    mutable_state: Mutex<RealtimeClientMutableState>,
+}
+
+/// Validated options produced by [`RealtimeClientBuilder`].
+pub(crate) struct ClientOptions {
+    pub(crate) heartbeat_interval: Duration,
+    pub(crate) timeout: Duration,
+    pub(crate) push_buffer_capacity: usize,
+    pub(crate) push_buffer_ttl: Duration,
+    pub(crate) connect_timeout: Duration,
+    pub(crate) reconnect_policy: Arc<dyn ReconnectPolicy>,
+    pub(crate) rejoin_policy: Arc<dyn ReconnectPolicy>,
+    pub(crate) token_provider: Option<Arc<dyn TokenProvider>>,
+    pub(crate) token_refresh_margin: Duration,
+    pub(crate) params: Vec<(String, String)>,
+    pub(crate) vsn: ProtocolVersion,
+    pub(crate) headers: HeaderMap,
+    pub(crate) logger: Logger,
 }
 
 #[derive(Clone, Default)]
 pub(crate) struct RealtimeClientMutableState {
     _ref: u32,
     channels: HashMap<String, RealtimeChannel>,
+    supervisor: Option<Task<()>>,
+    /// Refreshes the provided token before it expires. Only set once a token was provided.
+    token_refresh: Option<Task<()>>,
 }
 
 impl RealtimeClient {
@@ -37,22 +93,59 @@ impl RealtimeClient {
         max_retries: Option<u32>,
         initial_backoff: Option<f32>,
     ) -> Result<Self> {
-        if !is_ws_url(project_url) {
-            return Err(RealtimeError::InvalidUrlError);
+        let builder = Self::builder(project_url, api_key);
+
+        if !auto_reconnect.unwrap_or(true) {
+            return builder.reconnect_policy(NeverRetry).build();
         }
+
+        let initial_backoff = match initial_backoff {
+            Some(initial_backoff) => {
+                Duration::try_from_secs_f32(initial_backoff).map_err(|_| {
+                    invalid_option(
+                        "initial_backoff",
+                        "must be a non-negative number of seconds",
+                    )
+                })?
+            }
+            None => DEFAULT_INITIAL_BACKOFF,
+        };
+
+        builder
+            .reconnect_policy(
+                ExponentialBackoff::new(initial_backoff, DEFAULT_MAX_BACKOFF)
+                    .with_max_retries(max_retries.unwrap_or(DEFAULT_MAX_RETRIES)),
+            )
+            .build()
+    }
+
+    pub fn builder(project_url: &str, api_key: &str) -> RealtimeClientBuilder {
+        RealtimeClientBuilder::new(project_url, api_key)
+    }
+
+    pub(crate) fn with_options(project_url: &str, api_key: &str, options: ClientOptions) -> Self {
         let url = http_to_ws(project_url);
         // let http_endpoint = http_endpoint_url(project_url);
 
-        Ok(Self {
-            url,
-            api_key: String::from(api_key),
-            access_token: String::from(api_key),
-            auto_reconnect: auto_reconnect.unwrap_or(true),
-            max_retries: max_retries.unwrap_or(5),
-            initial_backoff: initial_backoff.unwrap_or(1.0),
-            connection: None,
-            mutable_state: Arc::new(Mutex::new(RealtimeClientMutableState::default())),
-        })
+        Self {
+            inner: Arc::new(RealtimeClientInner {
+                url,
+                api_key: String::from(api_key),
+                access_token: RwLock::new(String::from(api_key)),
+                options,
+                connection: RwLock::new(None),
+                connection_state: watch::Sender::new(ConnectionState::default()),
+                latency: Arc::new(HeartbeatLatency::new()),
+                decode_error_hooks: RwLock::default(),
+                mutable_state: Mutex::new(RealtimeClientMutableState::default()),
+            }),
+        }
+    }
+
+    pub(crate) fn downgrade(&self) -> WeakRealtimeClient {
+        WeakRealtimeClient {
+            inner: Arc::downgrade(&self.inner),
+        }
     }
 
     pub async fn create_channel(
@@ -64,120 +157,571 @@ impl RealtimeClient {
 
         let channel = RealtimeChannel::new(self, &topic, options);
         channel.register_default_events().await;
+        self.add_channel(&channel).await;
+
+        channel
+    }
 
-        self.mutable_state
+    pub(crate) async fn add_channel(&self, channel: &RealtimeChannel) {
+        self.inner
+            .mutable_state
             .lock()
             .await
             .channels
-            .insert(topic.clone(), channel.clone());
+            .insert(String::from(channel.get_topic()), channel.clone());
+    }
 
-        channel
+    /// Leaves `channel` and removes it from the client. Resolves with the server's reply to
+    /// `phx_leave`, or [`PushReply::TimedOut`] if none arrived within the client's timeout. See
+    /// [`RealtimeChannel::unsubscribe`] for when `phx_leave` isn't sent.
+    pub async fn remove_channel(&self, channel: &RealtimeChannel) -> Result<Option<PushReply>> {
+        let topic = channel.get_topic();
+
+        let Some(channel) = self.get_channel(topic).await else {
+            return Err(RealtimeError::ChannelNotFound {
+                topic: String::from(topic),
+            });
+        };
+
+        // the client's state is not kept locked while leaving, replies to the leave need it
+        let reply = channel.unsubscribe().await;
+        channel.clear_bindings().await;
+        self.inner.mutable_state.lock().await.channels.remove(topic);
+
+        reply
     }
 
-    pub async fn remove_channel(&mut self, channel: &RealtimeChannel) {
-        let topic = format!("realtime:{}", channel.get_topic());
+    /// Leaves and removes every channel, returning the outcome of each removal by topic.
+    pub async fn remove_all_channels(&self) -> HashMap<String, Result<Option<PushReply>>> {
+        let channels = self.get_channels().await;
+        let removals = channels.iter().map(|channel| async move {
+            let topic = String::from(channel.get_topic());
+            (topic, self.remove_channel(channel).await)
+        });
 
-        let channels = &self.mutable_state.lock().await.channels;
-        let channel = channels.get(&topic);
+        join_all(removals).await.into_iter().collect()
+    }
 
-        if let Some(channel) = channel {
-            channel.unsubscribe(self).await;
+    /// Replaces the access token used to join channels, and sends it to every joined channel.
+    /// Returns the error of each channel that failed to send the token, rejected it or didn't
+    /// reply within the client's timeout, by topic.
+    pub async fn set_auth(&self, token: &str) -> HashMap<String, RealtimeError> {
+        *self.inner.access_token.write().unwrap() = String::from(token);
+
+        let channels = self.get_channels().await;
+        let pushes = channels
+            .iter()
+            .filter(|channel| *channel.state_watch().borrow() == ChannelState::Joined)
+            .map(|channel| async move {
+                let topic = String::from(channel.get_topic());
+                let payload = Payload::AccessToken(AccessToken {
+                    access_token: String::from(token),
+                });
+
+                let result = match channel.send("access_token", payload).await {
+                    Ok(handle) => match handle.await {
+                        PushReply::Ok(_) => Ok(()),
+                        PushReply::Error(reason) => Err(RealtimeError::AccessTokenRejected {
+                            topic: topic.clone(),
+                            reason,
+                        }),
+                        PushReply::TimedOut => Err(RealtimeError::PushTimeout {
+                            event: String::from("access_token"),
+                        }),
+                    },
+                    Err(error) => Err(error),
+                };
+                (topic, result)
+            });
+
+        join_all(pushes)
+            .await
+            .into_iter()
+            .filter_map(|(topic, result)| Some((topic, result.err()?)))
+            .collect()
+    }
+
+    /// Asks the token provider for a token, unless one was provided that doesn't expire within
+    /// the refresh margin, and refreshes it from then on.
+    pub(crate) async fn ensure_access_token(&self) -> Result<()> {
+        let Some(token_provider) = self.inner.options.token_provider.clone() else {
+            return Ok(());
+        };
+
+        let provided = self
+            .inner
+            .mutable_state
+            .lock()
+            .await
+            .token_refresh
+            .is_some();
+        if provided && self.token_refresh_delay(&self.get_access_token()) > Duration::ZERO {
+            return Ok(());
+        }
+
+        let delay = self.refresh_access_token(token_provider.as_ref()).await?;
+        let token_refresh = Task::spawn(Self::refresh_tokens(self.downgrade(), delay));
+        if let Some(previous) = self
+            .inner
+            .mutable_state
+            .lock()
+            .await
+            .token_refresh
+            .replace(token_refresh)
+        {
+            previous.cancel();
+        }
+
+        Ok(())
+    }
+
+    /// Sets the token returned by `token_provider`. Returns the delay before it is due for a
+    /// refresh.
+    async fn refresh_access_token(&self, token_provider: &dyn TokenProvider) -> Result<Duration> {
+        let token = token_provider
+            .token()
+            .await
+            .map_err(|source| RealtimeError::TokenProviderError { source })?;
+
+        for (topic, error) in self.set_auth(&token).await {
+            self.log(
+                "auth",
+                &format!("Failed to send the refreshed token to {topic}: {error}"),
+            );
         }
+
+        // a token expiring within the margin would otherwise be refreshed in a loop
+        Ok(self.token_refresh_delay(&token).max(TOKEN_RETRY_DELAY))
+    }
+
+    /// Time left before `token` is due for a refresh. Tokens without an `exp` claim are never
+    /// refreshed.
+    fn token_refresh_delay(&self, token: &str) -> Duration {
+        let Some(expires_at) = expires_at(token) else {
+            return Duration::MAX;
+        };
+
+        expires_at
+            .duration_since(SystemTime::now())
+            .unwrap_or_default()
+            .saturating_sub(self.inner.options.token_refresh_margin)
     }
 
-    pub async fn remove_all_channels(&mut self) {
-        let channels = &self.mutable_state.lock().await.channels;
+    /// Refreshes the provided token whenever it is due, retrying while the token provider fails.
+    /// Stops once every client handle is dropped.
+    async fn refresh_tokens(client: WeakRealtimeClient, mut delay: Duration) {
+        while delay != Duration::MAX {
+            tokio::time::sleep(delay).await;
+
+            let Some(client) = client.upgrade() else {
+                return;
+            };
+            let Some(token_provider) = client.inner.options.token_provider.clone() else {
+                return;
+            };
 
-        for channel in channels.values() {
-            channel.unsubscribe(self).await;
+            delay = match client.refresh_access_token(token_provider.as_ref()).await {
+                Ok(delay) => delay,
+                Err(error) => {
+                    client.log("auth", &format!("{error}. Retrying."));
+                    TOKEN_RETRY_DELAY
+                }
+            };
         }
     }
 
     pub fn is_connected(&self) -> bool {
-        self.connection.is_some()
+        *self.inner.connection_state.borrow() == ConnectionState::Open
     }
 
-    pub(crate) fn get_access_token(&self) -> &str {
-        &self.access_token
+    /// Receives every change of the connection state, starting with the current one.
+    pub fn connection_state(&self) -> watch::Receiver<ConnectionState> {
+        self.inner.connection_state.subscribe()
     }
 
-    pub async fn connect(&mut self) -> Result<()> {
-        let url = format!("{}?apikey={}&vsn=1.0.0", self.url, self.api_key);
+    /// Round-trip time of the most recent heartbeat, if one has been replied to yet.
+    pub fn latency(&self) -> Option<Duration> {
+        self.inner.latency.last()
+    }
 
-        if self.is_connected() {
-            println!("Websocket client already connected.");
-            return Ok(());
+    /// Average heartbeat round-trip time over the last
+    /// [`LATENCY_WINDOW`](crate::latency::LATENCY_WINDOW) heartbeats.
+    pub fn average_latency(&self) -> Option<Duration> {
+        self.inner.latency.average()
+    }
+
+    /// Receives the round-trip time of every heartbeat replied to from now on.
+    pub fn subscribe_latency(&self) -> broadcast::Receiver<Duration> {
+        self.inner.latency.subscribe()
+    }
+
+    /// Called with every received frame that can't be decoded. The frame is skipped, and the
+    /// connection keeps receiving. Without hooks, the error is logged.
+    pub fn on_decode_error<F>(&self, f: F)
+    where
+        F: Fn(&RealtimeError) + Send + Sync + 'static,
+    {
+        self.inner
+            .decode_error_hooks
+            .write()
+            .unwrap()
+            .push(Arc::new(f));
+    }
+
+    fn on_decode_error_received(&self, error: RealtimeError) {
+        let hooks = self.inner.decode_error_hooks.read().unwrap().clone();
+        if hooks.is_empty() {
+            self.log("transport", &format!("Skipping frame. {error}"));
         }
 
-        let mut backoff = self.initial_backoff;
-        let mut last_error = None;
+        for hook in hooks {
+            hook(&error);
+        }
+    }
 
-        println!("Attempting to connect to websocket at {url}.");
+    pub(crate) fn get_access_token(&self) -> String {
+        self.inner.access_token.read().unwrap().clone()
+    }
 
-        for attempt in 0..self.max_retries {
-            let mutable_state = self.mutable_state.clone();
-            let message_received_callback = move |message: Message| {
-                let mutable_state = mutable_state.clone();
-                Box::pin(async move {
-                    let channels = &mut mutable_state.lock().await.channels;
-                    Self::on_receive(channels, message).await;
-                }) as Pin<Box<dyn Future<Output = ()> + Send>>
-            };
+    pub(crate) fn get_timeout(&self) -> Duration {
+        self.inner.options.timeout
+    }
 
-            // TODO: pass heartbeat interval option
-            let result =
-                RealtimeConnection::new(&url, Box::new(message_received_callback), None).await;
-            match result {
-                Ok(connection) => {
-                    self.connection = Some(connection);
-                    println!("Websockets connection established successfully.");
-                    return Ok(());
+    pub(crate) fn get_push_buffer_capacity(&self) -> usize {
+        self.inner.options.push_buffer_capacity
+    }
+
+    pub(crate) fn get_vsn(&self) -> ProtocolVersion {
+        self.inner.options.vsn
+    }
+
+    pub(crate) fn get_push_buffer_ttl(&self) -> Duration {
+        self.inner.options.push_buffer_ttl
+    }
+
+    pub(crate) fn get_rejoin_policy(&self) -> &dyn ReconnectPolicy {
+        self.inner.options.rejoin_policy.as_ref()
+    }
+
+    pub(crate) fn get_logger(&self) -> Logger {
+        self.inner.options.logger.clone()
+    }
+
+    pub(crate) fn log(&self, kind: &str, message: &str) {
+        (self.inner.options.logger)(kind, message);
+    }
+
+    pub async fn connect(&self) -> Result<()> {
+        // let an ongoing (re)connection finish instead of racing it
+        let mut connection_state = self.connection_state();
+        loop {
+            let state = *connection_state
+                .wait_for(|state| matches!(state, ConnectionState::Open | ConnectionState::Closed))
+                .await
+                .map_err(|_| RealtimeError::ConnectionClosed)?;
+
+            if state == ConnectionState::Open {
+                self.log("transport", "Websocket client already connected.");
+                return Ok(());
+            }
+
+            // only one of the concurrent calls moves the state out of `Closed`, the others wait
+            // for it to connect
+            let claimed = self.inner.connection_state.send_if_modified(|state| {
+                let claimed = *state == ConnectionState::Closed;
+                if claimed {
+                    *state = ConnectionState::Connecting;
                 }
-                Err(error) => {
-                    println!("Connection attempt failed: {error}");
-                    last_error = Some(error);
+                claimed
+            });
+            if claimed {
+                break;
+            }
+        }
+
+        if let Err(error) = self.ensure_access_token().await {
+            set_connection_state(&self.inner.connection_state, ConnectionState::Closed);
+            return Err(error);
+        }
+
+        let connection = Self::connect_with_retries(&self.downgrade(), 0).await?;
+        self.set_connection(connection);
+
+        let supervisor = Task::spawn(Self::supervise(self.downgrade()));
+        if let Some(previous) = self
+            .inner
+            .mutable_state
+            .lock()
+            .await
+            .supervisor
+            .replace(supervisor)
+        {
+            previous.cancel();
+        }
+
+        Ok(())
+    }
 
-                    if !self.auto_reconnect {
-                        println!("{}", self.auto_reconnect);
+    /// Stores `connection` as the current one, stopping the one it replaces, and only then
+    /// publishes that the client is connected.
+    fn set_connection(&self, connection: RealtimeConnection) {
+        let connected = !connection.is_closed();
+        if let Some(previous) = self.inner.connection.write().unwrap().replace(connection) {
+            previous.discard();
+        }
+
+        if connected {
+            set_connection_state(&self.inner.connection_state, ConnectionState::Open);
+        }
+    }
+
+    /// Connects, retrying as long as the reconnect policy allows. `attempt` is the number of
+    /// attempts already made, so a reconnection starts by backing off. The client is only held
+    /// during an attempt, not while backing off, so that dropping it stops the retries.
+    async fn connect_with_retries(
+        client: &WeakRealtimeClient,
+        mut attempt: u32,
+    ) -> Result<RealtimeConnection> {
+        let url = {
+            let client = client.upgrade().ok_or(RealtimeError::ClientDropped)?;
+            let url = client.connection_url();
+            client.log(
+                "transport",
+                &format!("Attempting to connect to websocket at {url}."),
+            );
+            url
+        };
+        let reconnecting = attempt > 0;
+        let mut last_error = None;
+
+        loop {
+            let connection_state = if reconnecting {
+                ConnectionState::Reconnecting { attempt }
+            } else {
+                ConnectionState::Connecting
+            };
+
+            if attempt > 0 {
+                let delay = {
+                    let client = client.upgrade().ok_or(RealtimeError::ClientDropped)?;
+                    let Some(delay) = client.inner.options.reconnect_policy.next_delay(attempt)
+                    else {
                         break;
-                    }
+                    };
 
-                    let wait_time = backoff * (2.0 * attempt as f32);
-                    println!(
-                        "Retry {}/{}: Next attempt in {wait_time}s (backoff={backoff}s)",
-                        attempt + 1,
-                        self.max_retries
+                    set_connection_state(&client.inner.connection_state, connection_state);
+                    client.log(
+                        "transport",
+                        &format!("Retry {attempt}: Next attempt in {}s", delay.as_secs_f32()),
                     );
-                    tokio::time::sleep(tokio::time::Duration::from_secs_f32(wait_time)).await;
-                    backoff = f32::min(backoff * 2.0, 60.0);
+                    delay
+                };
+                tokio::time::sleep(delay).await;
+            }
+            attempt += 1;
+
+            let client = client.upgrade().ok_or(RealtimeError::ClientDropped)?;
+            set_connection_state(&client.inner.connection_state, connection_state);
+            match client.try_connect(&url).await {
+                Ok(connection) => {
+                    client.log(
+                        "transport",
+                        "Websockets connection established successfully.",
+                    );
+                    return Ok(connection);
+                }
+                Err(error) => {
+                    client.log("transport", &format!("Connection attempt failed: {error}"));
+                    last_error = Some(error);
                 }
             }
         }
 
-        let error = last_error.unwrap();
-        println!(
-            "Connection failed after {} attempts. Error {error}",
-            self.max_retries
+        let client = client.upgrade().ok_or(RealtimeError::ClientDropped)?;
+        set_connection_state(&client.inner.connection_state, ConnectionState::Closed);
+
+        let error = last_error.unwrap_or(RealtimeError::ConnectionClosed);
+        client.log(
+            "transport",
+            &format!(
+                "Giving up connecting after {} retries. Error {error}",
+                attempt - 1
+            ),
         );
         Err(error)
     }
 
-    pub async fn close(self) -> Result<()> {
-        if let Some(connection) = self.connection {
-            return connection.close().await;
+    async fn try_connect(&self, url: &str) -> Result<RealtimeConnection> {
+        let client = self.downgrade();
+        let message_received_callback = move |message: Result<Message>| {
+            let client = client.clone();
+            Box::pin(async move {
+                let Some(client) = client.upgrade() else {
+                    return;
+                };
+
+                match message {
+                    Ok(message) => client.on_receive(message).await,
+                    Err(error) => client.on_decode_error_received(error),
+                }
+            }) as Pin<Box<dyn Future<Output = ()> + Send>>
+        };
+
+        let request = self.connection_request(url)?;
+        tokio::time::timeout(
+            self.inner.options.connect_timeout,
+            RealtimeConnection::new(
+                request,
+                Box::new(message_received_callback),
+                Some(interval(self.inner.options.heartbeat_interval)),
+                self.inner.latency.clone(),
+                self.inner.options.logger.clone(),
+                self.inner.connection_state.clone(),
+                self.inner.options.vsn,
+            ),
+        )
+        .await
+        .unwrap_or(Err(RealtimeError::ConnectTimeout))
+    }
+
+    fn connection_url(&self) -> String {
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+        query
+            .append_pair("apikey", &self.inner.api_key)
+            .append_pair("vsn", &self.inner.options.vsn.to_string());
+        for (key, value) in &self.inner.options.params {
+            query.append_pair(key, value);
         }
 
-        Ok(())
+        format!("{}?{}", self.inner.url, query.finish())
+    }
+
+    fn connection_request(&self, url: &str) -> Result<Request<()>> {
+        let mut request = url.into_client_request()?;
+        request
+            .headers_mut()
+            .extend(self.inner.options.headers.clone());
+
+        Ok(request)
     }
 
-    async fn on_receive(channels: &mut HashMap<String, RealtimeChannel>, message: Message) {
+    /// Watches the current connection and, once it is lost, re-establishes it and rejoins every
+    /// channel that was joined before the loss. Stops once every client handle is dropped.
+    async fn supervise(client: WeakRealtimeClient) {
+        loop {
+            let closed = {
+                let Some(client) = client.upgrade() else {
+                    return;
+                };
+                match &*client.inner.connection.read().unwrap() {
+                    Some(connection) => connection.closed(),
+                    None => return,
+                }
+            };
+            closed.await;
+
+            if !Self::reconnect(&client).await {
+                return;
+            }
+        }
+    }
+
+    /// Replaces the lost connection and rejoins the channels. Returns `false` if the reconnect
+    /// policy gave up or the client was dropped.
+    async fn reconnect(client: &WeakRealtimeClient) -> bool {
+        let channels = {
+            let Some(client) = client.upgrade() else {
+                return false;
+            };
+
+            let connection = client.inner.connection.write().unwrap().take();
+            if let Some(connection) = connection
+                && let Err(error) = connection.stopped().await
+            {
+                client.log("transport", &format!("Websocket connection lost: {error}"));
+            }
+
+            let channels = client.get_channels().await;
+            for channel in &channels {
+                channel.on_connection_lost().await;
+            }
+            channels
+        };
+
+        let connection = Self::connect_with_retries(client, 1).await;
+        let Some(client) = client.upgrade() else {
+            return false;
+        };
+        match connection {
+            Ok(connection) => client.set_connection(connection),
+            Err(error) => {
+                client.log("transport", &format!("Reconnection failed: {error}"));
+                for channel in &channels {
+                    channel.on_connection_closed().await;
+                }
+                return false;
+            }
+        }
+
+        for mut channel in channels {
+            if let Err(error) = channel.rejoin_after_reconnect(&client).await {
+                client.log(
+                    "channel",
+                    &format!("Failed to rejoin {}: {error}", channel.get_topic()),
+                );
+            }
+        }
+
+        true
+    }
+
+    pub async fn close(&self) -> Result<()> {
+        {
+            let mut mutable_state = self.inner.mutable_state.lock().await;
+            if let Some(supervisor) = mutable_state.supervisor.take() {
+                supervisor.cancel();
+            }
+            // the token is provided again on the next connection
+            if let Some(token_refresh) = mutable_state.token_refresh.take() {
+                token_refresh.cancel();
+            }
+        }
+
+        let connection = self.inner.connection.write().unwrap().take();
+        let result = match connection {
+            Some(connection) => connection.close().await,
+            None => {
+                set_connection_state(&self.inner.connection_state, ConnectionState::Closed);
+                Ok(())
+            }
+        };
+
+        // the server dropped them along with the connection
+        for channel in self.get_channels().await {
+            channel.on_connection_closed().await;
+        }
+
+        result
+    }
+
+    async fn on_receive(&self, message: Message) {
+        if let Payload::Unknown { event, .. } = &message.payload {
+            self.log(
+                "channel",
+                &format!("Received unknown event {event} on {}.", message.topic),
+            );
+        }
+
+        let channels = &mut self.inner.mutable_state.lock().await.channels;
         let channel = channels.remove(&message.topic);
 
         if let Some(channel) = channel {
             let mut should_remove_channel = false;
             channel
                 .trigger(
+                    self,
                     message.payload,
+                    message.join_ref.as_deref(),
                     message.ref_field.as_deref(),
                     &mut should_remove_channel,
                 )
@@ -189,17 +733,61 @@ impl RealtimeClient {
         }
     }
 
+    pub(crate) async fn get_channel(&self, topic: &str) -> Option<RealtimeChannel> {
+        self.inner
+            .mutable_state
+            .lock()
+            .await
+            .channels
+            .get(topic)
+            .cloned()
+    }
+
+    async fn get_channels(&self) -> Vec<RealtimeChannel> {
+        self.inner
+            .mutable_state
+            .lock()
+            .await
+            .channels
+            .values()
+            .cloned()
+            .collect()
+    }
+
     pub(crate) async fn make_ref(&self) -> String {
-        let mut state = self.mutable_state.lock().await;
+        let mut state = self.inner.mutable_state.lock().await;
         state._ref += 1;
         state._ref.to_string()
     }
 
     pub(crate) fn send(&self, message: Message) -> Result<()> {
-        if let Some(connection) = &self.connection {
+        if let Some(connection) = &*self.inner.connection.read().unwrap() {
             connection.send(message)
         } else {
             Err(RealtimeError::ConnectionClosed)
         }
     }
 }
+
+impl WeakRealtimeClient {
+    /// Returns `None` once every client handle is dropped.
+    pub(crate) fn upgrade(&self) -> Option<RealtimeClient> {
+        self.inner.upgrade().map(|inner| RealtimeClient { inner })
+    }
+}
+
+impl Drop for RealtimeClientInner {
+    fn drop(&mut self) {
+        if let Some(supervisor) = self.mutable_state.get_mut().supervisor.take() {
+            supervisor.cancel();
+        }
+
+        if let Some(token_refresh) = self.mutable_state.get_mut().token_refresh.take() {
+            token_refresh.cancel();
+        }
+
+        if let Some(connection) = self.connection.get_mut().unwrap().take() {
+            connection.cancel();
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio_tungstenite::tungstenite::http::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::{
+    client::{ClientOptions, RealtimeClient},
+    error::RealtimeError,
+    reconnect_policy::{ExponentialBackoff, ReconnectPolicy},
+    token_provider::TokenProvider,
+    types::{
+        DEFAULT_CONNECT_TIMEOUT, DEFAULT_HEARTBEAT_INTERVAL, DEFAULT_PUSH_BUFFER_CAPACITY,
+        DEFAULT_PUSH_BUFFER_TTL, DEFAULT_REJOIN_INITIAL_BACKOFF, DEFAULT_REJOIN_MAX_BACKOFF,
+        DEFAULT_TIMEOUT, DEFAULT_TOKEN_REFRESH_MARGIN, Logger, ProtocolVersion, Result,
+    },
+    utils::is_ws_url,
+};
+
+/// Configures and creates a [`RealtimeClient`].
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use supabase_realtime_rs::client::RealtimeClient;
+/// # fn main() -> supabase_realtime_rs::types::Result<()> {
+/// let client = RealtimeClient::builder("http://127.0.0.1:54321", "api-key")
+///     .heartbeat_interval(Duration::from_secs(15))
+///     .param("log_level", "info")
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct RealtimeClientBuilder {
+    project_url: String,
+    api_key: String,
+    heartbeat_interval: Duration,
+    timeout: Duration,
+    push_buffer_capacity: usize,
+    push_buffer_ttl: Duration,
+    connect_timeout: Duration,
+    reconnect_policy: Arc<dyn ReconnectPolicy>,
+    rejoin_policy: Arc<dyn ReconnectPolicy>,
+    token_provider: Option<Arc<dyn TokenProvider>>,
+    token_refresh_margin: Duration,
+    params: Vec<(String, String)>,
+    vsn: ProtocolVersion,
+    headers: Vec<(String, String)>,
+    logger: Option<Logger>,
+}
+
+impl RealtimeClientBuilder {
+    pub(crate) fn new(project_url: &str, api_key: &str) -> Self {
+        Self {
+            project_url: String::from(project_url),
+            api_key: String::from(api_key),
+            heartbeat_interval: DEFAULT_HEARTBEAT_INTERVAL,
+            timeout: DEFAULT_TIMEOUT,
+            push_buffer_capacity: DEFAULT_PUSH_BUFFER_CAPACITY,
+            push_buffer_ttl: DEFAULT_PUSH_BUFFER_TTL,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            reconnect_policy: Arc::new(ExponentialBackoff::default()),
+            rejoin_policy: Arc::new(ExponentialBackoff::new(
+                DEFAULT_REJOIN_INITIAL_BACKOFF,
+                DEFAULT_REJOIN_MAX_BACKOFF,
+            )),
+            token_provider: None,
+            token_refresh_margin: DEFAULT_TOKEN_REFRESH_MARGIN,
+            params: vec![],
+            vsn: ProtocolVersion::default(),
+            headers: vec![],
+            logger: None,
+        }
+    }
+
+    /// How often a heartbeat is sent. A heartbeat that is not replied to before the next one is
+    /// due closes the connection.
+    pub fn heartbeat_interval(mut self, heartbeat_interval: Duration) -> Self {
+        self.heartbeat_interval = heartbeat_interval;
+        self
+    }
+
+    /// How long a channel push waits for the server's reply.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How many pushes a channel keeps while it is not joined, to send them once it is. Pushes
+    /// made while the buffer is full fail with [`RealtimeError::PushBufferFull`].
+    pub fn push_buffer_capacity(mut self, push_buffer_capacity: usize) -> Self {
+        self.push_buffer_capacity = push_buffer_capacity;
+        self
+    }
+
+    /// How long a push may wait in the buffer before it times out without being sent.
+    pub fn push_buffer_ttl(mut self, push_buffer_ttl: Duration) -> Self {
+        self.push_buffer_ttl = push_buffer_ttl;
+        self
+    }
+
+    /// How long a single attempt at opening the websocket may take.
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    /// How long to wait between attempts at connecting, both initially and after the connection
+    /// is lost. Defaults to [`ExponentialBackoff::default`].
+    pub fn reconnect_policy<P>(mut self, reconnect_policy: P) -> Self
+    where
+        P: ReconnectPolicy + 'static,
+    {
+        self.reconnect_policy = Arc::new(reconnect_policy);
+        self
+    }
+
+    /// How long an errored or timed out channel waits before sending `phx_join` again. Defaults
+    /// to exponential backoff between 1 and 10 seconds, retrying until the channel is joined or
+    /// left.
+    pub fn rejoin_policy<P>(mut self, rejoin_policy: P) -> Self
+    where
+        P: ReconnectPolicy + 'static,
+    {
+        self.rejoin_policy = Arc::new(rejoin_policy);
+        self
+    }
+
+    /// Fetches the access token used to join channels, instead of using the API key, and
+    /// refreshes it before it expires.
+    pub fn token_provider<P>(mut self, token_provider: P) -> Self
+    where
+        P: TokenProvider + 'static,
+    {
+        self.token_provider = Some(Arc::new(token_provider));
+        self
+    }
+
+    /// How long before its `exp` claim a token from the token provider is refreshed.
+    pub fn token_refresh_margin(mut self, token_refresh_margin: Duration) -> Self {
+        self.token_refresh_margin = token_refresh_margin;
+        self
+    }
+
+    /// Adds a query parameter to the websocket URL, e.g. `log_level`.
+    pub fn param(mut self, key: &str, value: &str) -> Self {
+        self.params.push((String::from(key), String::from(value)));
+        self
+    }
+
+    pub fn vsn(mut self, vsn: ProtocolVersion) -> Self {
+        self.vsn = vsn;
+        self
+    }
+
+    /// Adds a header to the websocket handshake request.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((String::from(name), String::from(value)));
+        self
+    }
+
+    /// Replaces the default logger, which prints to stdout.
+    pub fn logger<F>(mut self, logger: F) -> Self
+    where
+        F: Fn(&str, &str) + Send + Sync + 'static,
+    {
+        self.logger = Some(Arc::new(logger));
+        self
+    }
+
+    pub fn build(self) -> Result<RealtimeClient> {
+        if !is_ws_url(&self.project_url) {
+            return Err(RealtimeError::InvalidUrlError);
+        }
+
+        for (option, duration) in [
+            ("heartbeat_interval", self.heartbeat_interval),
+            ("timeout", self.timeout),
+            ("push_buffer_ttl", self.push_buffer_ttl),
+            ("connect_timeout", self.connect_timeout),
+        ] {
+            if duration.is_zero() {
+                return Err(invalid_option(option, "must be greater than zero"));
+            }
+        }
+
+        if let Some((key, _)) = self
+            .params
+            .iter()
+            .find(|(key, _)| key.is_empty() || key == "apikey" || key == "vsn")
+        {
+            return Err(invalid_option(
+                "param",
+                &format!("'{key}' cannot be set as an extra parameter"),
+            ));
+        }
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let header_name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|error| invalid_option("header", &format!("'{name}': {error}")))?;
+            let header_value = HeaderValue::from_str(value)
+                .map_err(|error| invalid_option("header", &format!("'{name}': {error}")))?;
+            headers.append(header_name, header_value);
+        }
+
+        let logger = self
+            .logger
+            .unwrap_or_else(|| Arc::new(|_, message| println!("{message}")));
+
+        let options = ClientOptions {
+            heartbeat_interval: self.heartbeat_interval,
+            timeout: self.timeout,
+            push_buffer_capacity: self.push_buffer_capacity,
+            push_buffer_ttl: self.push_buffer_ttl,
+            connect_timeout: self.connect_timeout,
+            reconnect_policy: self.reconnect_policy,
+            rejoin_policy: self.rejoin_policy,
+            token_provider: self.token_provider,
+            token_refresh_margin: self.token_refresh_margin,
+            params: self.params,
+            vsn: self.vsn,
+            headers,
+            logger,
+        };
+
+        Ok(RealtimeClient::with_options(
+            &self.project_url,
+            &self.api_key,
+            options,
+        ))
+    }
+}
+
+pub(crate) fn invalid_option(option: &str, reason: &str) -> RealtimeError {
+    RealtimeError::InvalidClientOption {
+        option: String::from(option),
+        reason: String::from(reason),
+    }
+}
@@ -1,26 +1,48 @@
+use bytes::Bytes;
 use futures::{
     SinkExt, StreamExt,
     stream::{SplitSink, SplitStream},
 };
+use std::{sync::Arc, time::Instant};
+
 use tokio::{
     net::TcpStream,
-    sync::mpsc::{UnboundedReceiver, UnboundedSender},
+    sync::{
+        Mutex,
+        mpsc::{UnboundedReceiver, UnboundedSender},
+        watch,
+    },
     task::JoinHandle,
     time::{Interval, interval},
 };
 use tokio_tungstenite::{
-    MaybeTlsStream, WebSocketStream, connect_async, tungstenite::Message as TMessage,
+    MaybeTlsStream, WebSocketStream, connect_async,
+    tungstenite::{Message as TMessage, http::Request},
 };
-use tokio_util::sync::CancellationToken;
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
 
 use crate::{
     error::RealtimeError,
+    latency::HeartbeatLatency,
     protocol_objects::{Heartbeat, Message, Payload},
-    types::{ConnectionMessageReceivedEvent, DEFAULT_HEARTBEAT_INTERVAL, Result},
+    serializer,
+    types::{
+        ConnectionMessageReceivedEvent, ConnectionState, DEFAULT_HEARTBEAT_INTERVAL, Logger,
+        ProtocolVersion, Result,
+    },
 };
 
+const PHOENIX_TOPIC: &str = "phoenix";
+
+/// A heartbeat that was sent and has not been replied to yet.
+struct PendingHeartbeat {
+    _ref: String,
+    sent_at: Instant,
+}
+
 pub struct RealtimeConnection {
     sender: UnboundedSender<Message>,
+    connection_state: watch::Sender<ConnectionState>,
     listen_join_handle: JoinHandle<Result<()>>,
     send_join_handle: JoinHandle<Result<()>>,
     heartbeat_join_handle: JoinHandle<Result<()>>,
@@ -39,39 +61,57 @@ pub struct RealtimeConnection {
 // }
 
 impl RealtimeConnection {
-    pub async fn new(
-        url: &str,
+    pub(crate) async fn new(
+        request: Request<()>,
         message_received_callback: ConnectionMessageReceivedEvent,
         heartbeat_interval: Option<Interval>,
+        latency: Arc<HeartbeatLatency>,
+        logger: Logger,
+        connection_state: watch::Sender<ConnectionState>,
+        vsn: ProtocolVersion,
     ) -> Result<Self> {
         let heartbeat_interval = heartbeat_interval.unwrap_or(interval(DEFAULT_HEARTBEAT_INTERVAL));
 
-        let (ws_stream, _) = connect_async(url).await?;
+        // the client publishes `Open` once it has stored the connection
+        let (ws_stream, _) = connect_async(request).await?;
+
         let (ws_sender, ws_receiver) = ws_stream.split();
         let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
 
         let cancellation_token = CancellationToken::new();
+        let pending_heartbeat = Arc::new(Mutex::new(None));
 
         // TODO: need to handle errors for join handles
         let listen_join_handle = tokio::spawn(Self::listen(
             ws_receiver,
             message_received_callback,
+            pending_heartbeat.clone(),
+            latency,
+            logger.clone(),
+            connection_state.clone(),
             cancellation_token.clone(),
         ));
         let send_join_handle = tokio::spawn(Self::ws_send_loop(
             receiver,
             ws_sender,
+            vsn,
+            logger.clone(),
+            connection_state.clone(),
             cancellation_token.clone(),
         ));
 
         let heartbeat_join_handle = tokio::spawn(Self::heartbeat(
             sender.clone(),
+            pending_heartbeat,
+            logger,
+            connection_state.clone(),
             cancellation_token.clone(),
             heartbeat_interval,
         ));
 
         Ok(Self {
             sender,
+            connection_state,
             listen_join_handle,
             send_join_handle,
             heartbeat_join_handle,
@@ -80,9 +120,25 @@ impl RealtimeConnection {
     }
 
     pub async fn close(self) -> Result<()> {
-        self.cancellation_token.cancel();
+        if !self.is_closed() {
+            set_connection_state(&self.connection_state, ConnectionState::Closing);
+            self.cancellation_token.cancel();
+        }
+
+        let connection_state = self.connection_state.clone();
+        let result = self.stopped().await;
+        set_connection_state(&connection_state, ConnectionState::Closed);
+        result
+    }
 
-        let results = tokio::try_join!(self.listen_join_handle, self.heartbeat_join_handle)?;
+    /// Waits for the tasks of a connection that stopped, returning their errors. Unlike
+    /// [`close`](Self::close), the connection state is left as is.
+    pub(crate) async fn stopped(self) -> Result<()> {
+        let results = tokio::try_join!(
+            self.listen_join_handle,
+            self.send_join_handle,
+            self.heartbeat_join_handle
+        )?;
 
         let mut task_errors = vec![];
 
@@ -92,6 +148,9 @@ impl RealtimeConnection {
         if let Err(error) = results.1 {
             task_errors.push(error);
         }
+        if let Err(error) = results.2 {
+            task_errors.push(error);
+        }
 
         if !task_errors.is_empty() {
             return Err(RealtimeError::MultipleTaskErrors {
@@ -102,15 +161,58 @@ impl RealtimeConnection {
         Ok(())
     }
 
+    /// Stops the connection tasks without waiting for them to finish.
+    pub(crate) fn cancel(&self) {
+        set_connection_state(&self.connection_state, ConnectionState::Closed);
+        self.cancellation_token.cancel();
+    }
+
+    /// Stops the connection tasks of a connection that was replaced, leaving the connection state
+    /// to its replacement.
+    pub(crate) fn discard(&self) {
+        self.cancellation_token.cancel();
+    }
+
+    /// Whether any of the connection tasks has stopped, either because the socket was lost or
+    /// because the connection was closed.
+    pub(crate) fn is_closed(&self) -> bool {
+        self.cancellation_token.is_cancelled()
+    }
+
+    /// Resolves once the connection stops, either because the socket was lost or because the
+    /// connection was closed.
+    pub(crate) fn closed(&self) -> WaitForCancellationFutureOwned {
+        self.cancellation_token.clone().cancelled_owned()
+    }
+
     async fn listen(
         mut ws_receiver: SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>,
         message_received_callback: ConnectionMessageReceivedEvent,
+        pending_heartbeat: Arc<Mutex<Option<PendingHeartbeat>>>,
+        latency: Arc<HeartbeatLatency>,
+        logger: Logger,
+        connection_state: watch::Sender<ConnectionState>,
         cancellation_token: CancellationToken,
     ) -> Result<()> {
         loop {
             tokio::select! {
                 received = ws_receiver.next() => {
-                    Self::handle_receive(received, &message_received_callback).await?;
+                    let result = Self::handle_receive(
+                        received,
+                        &message_received_callback,
+                        &pending_heartbeat,
+                        &latency,
+                    )
+                    .await;
+
+                    if let Err(error) = result {
+                        logger(
+                            "transport",
+                            &format!("connection: canceling due to receive error: {error}"),
+                        );
+                        Self::fail(&connection_state, &cancellation_token);
+                        return Err(error);
+                    }
                 }
 
                 _ = cancellation_token.cancelled() => {
@@ -125,17 +227,36 @@ impl RealtimeConnection {
     async fn ws_send_loop(
         mut receiver: UnboundedReceiver<Message>,
         mut ws_sender: SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, TMessage>,
+        vsn: ProtocolVersion,
+        logger: Logger,
+        connection_state: watch::Sender<ConnectionState>,
         cancellation_token: CancellationToken,
     ) -> Result<()> {
         loop {
             tokio::select! {
                 message = receiver.recv() => {
                     if let Some(message) = message {
-                        let tmessage = Self::message_to_tmessage(&message)?;
-                        ws_sender.send(tmessage).await?;
+                        let tmessage = match Self::message_to_tmessage(&message, vsn) {
+                            Ok(tmessage) => tmessage,
+                            Err(error) => {
+                                logger("transport", &format!("Skipping message. {error}"));
+                                continue;
+                            }
+                        };
+                        if let Err(error) = ws_sender.send(tmessage).await {
+                            logger(
+                                "transport",
+                                &format!("connection: canceling due to send error: {error}"),
+                            );
+                            Self::fail(&connection_state, &cancellation_token);
+                            return Err(error.into());
+                        }
                     }else {
-                        println!("connection: canceling due to None received from receiver.");
-                        cancellation_token.cancel();
+                        logger(
+                            "transport",
+                            "connection: canceling due to None received from receiver.",
+                        );
+                        Self::fail(&connection_state, &cancellation_token);
                         break;
                     }
                 },
@@ -150,22 +271,53 @@ impl RealtimeConnection {
         Ok(())
     }
 
+    /// Sends a heartbeat every interval. If the previous heartbeat was not replied to by the time
+    /// the next one is due, the connection is considered dead and is closed.
     async fn heartbeat(
         sender: UnboundedSender<Message>,
+        pending_heartbeat: Arc<Mutex<Option<PendingHeartbeat>>>,
+        logger: Logger,
+        connection_state: watch::Sender<ConnectionState>,
         cancellation_token: CancellationToken,
         mut interval: Interval,
     ) -> Result<()> {
         interval.tick().await;
 
+        let mut heartbeat_ref: u32 = 0;
+
         loop {
             tokio::select! {
                 _ = interval.tick() => {
+                    let mut pending_heartbeat = pending_heartbeat.lock().await;
+                    if let Some(missed) = &*pending_heartbeat {
+                        logger(
+                            "transport",
+                            &format!(
+                                "connection: canceling due to missed reply for heartbeat {}.",
+                                missed._ref
+                            ),
+                        );
+                        Self::fail(&connection_state, &cancellation_token);
+                        return Err(RealtimeError::HeartbeatError);
+                    }
+
+                    heartbeat_ref += 1;
+                    let _ref = heartbeat_ref.to_string();
+                    *pending_heartbeat = Some(PendingHeartbeat {
+                        _ref: _ref.clone(),
+                        sent_at: Instant::now(),
+                    });
+
                     let heartbeat_message = Message {
-                        topic: String::from("phoenix"),
+                        topic: String::from(PHOENIX_TOPIC),
                         payload: Payload::Heartbeat(Heartbeat {}),
-                        ref_field: None,
+                        ref_field: Some(_ref),
+                        join_ref: None,
                     };
-                    sender.send(heartbeat_message).unwrap(); // TODO: error handling?
+                    if let Err(error) = sender.send(heartbeat_message) {
+                        Self::fail(&connection_state, &cancellation_token);
+                        return Err(error.into());
+                    }
                 },
 
                 _ = cancellation_token.cancelled() => {
@@ -175,6 +327,24 @@ impl RealtimeConnection {
         }
     }
 
+    /// Stops all the tasks of a lost connection, unless it is already closing. The client's
+    /// supervisor re-establishes it, so the state moves to `Reconnecting` rather than `Closed`,
+    /// which would let `connect` open a second connection meanwhile.
+    fn fail(
+        connection_state: &watch::Sender<ConnectionState>,
+        cancellation_token: &CancellationToken,
+    ) {
+        if cancellation_token.is_cancelled() {
+            return;
+        }
+
+        set_connection_state(
+            connection_state,
+            ConnectionState::Reconnecting { attempt: 0 },
+        );
+        cancellation_token.cancel();
+    }
+
     pub(crate) fn send(&self, message: Message) -> Result<()> {
         Ok(self.sender.send(message)?)
     }
@@ -182,12 +352,26 @@ impl RealtimeConnection {
     async fn handle_receive(
         received: Option<std::result::Result<TMessage, tokio_tungstenite::tungstenite::Error>>,
         message_received_callback: &ConnectionMessageReceivedEvent,
+        pending_heartbeat: &Mutex<Option<PendingHeartbeat>>,
+        latency: &HeartbeatLatency,
     ) -> Result<()> {
         match received {
             Some(received) => match received {
                 Ok(message) => match message {
                     TMessage::Text(text) => {
-                        let message = Self::tmessage_text_to_message(&text)?;
+                        // a frame that can't be decoded is skipped, it doesn't stop the connection
+                        let message = match Self::tmessage_text_to_message(&text) {
+                            Ok(message) => message,
+                            Err(error) => {
+                                message_received_callback(Err(error)).await;
+                                return Ok(());
+                            }
+                        };
+                        if message.topic == PHOENIX_TOPIC {
+                            Self::on_heartbeat_reply(&message, pending_heartbeat, latency).await;
+                            return Ok(());
+                        }
+
                         // if matches!(
                         //     message.payload,
                         //     Payload::PhxReply(PhxReply::Ok(PhxResponse {
@@ -197,24 +381,61 @@ impl RealtimeConnection {
                         //     println!("Skipping postgres changes reply");
                         //     Ok(())
                         // } else {
-                        message_received_callback(message).await;
+                        message_received_callback(Ok(message)).await;
                         Ok(())
                         // }
                     }
-                    TMessage::Close(_) => Ok(()),
+                    TMessage::Binary(data) => {
+                        match serializer::decode_binary(Bytes::from(data)) {
+                            Ok(message) => message_received_callback(Ok(message)).await,
+                            Err(error) => message_received_callback(Err(error)).await,
+                        }
+                        Ok(())
+                    }
+                    TMessage::Close(_) => Err(RealtimeError::ConnectionClosed),
                     _ => Ok(()),
                 },
                 Err(error) => Err(error.into()),
             },
-            None => Ok(()),
+            None => Err(RealtimeError::ConnectionClosed),
+        }
+    }
+
+    async fn on_heartbeat_reply(
+        message: &Message,
+        pending_heartbeat: &Mutex<Option<PendingHeartbeat>>,
+        latency: &HeartbeatLatency,
+    ) {
+        if !matches!(message.payload, Payload::PhxReply(_)) {
+            return;
+        }
+
+        let mut pending_heartbeat = pending_heartbeat.lock().await;
+        if let Some(pending) = &*pending_heartbeat
+            && message.ref_field.as_deref() == Some(pending._ref.as_str())
+        {
+            latency.record(pending.sent_at.elapsed());
+            *pending_heartbeat = None;
         }
     }
 
-    fn message_to_tmessage(message: &Message) -> Result<TMessage> {
-        Ok(TMessage::Text(serde_json::to_string(message)?))
+    fn message_to_tmessage(message: &Message, vsn: ProtocolVersion) -> Result<TMessage> {
+        serializer::encode(message, vsn)
     }
 
     fn tmessage_text_to_message(tmessage: &str) -> Result<Message> {
-        Ok(serde_json::from_str(tmessage)?)
+        serializer::decode(tmessage)
     }
 }
+
+/// Publishes `new_state`, notifying receivers only if it differs from the current state.
+pub(crate) fn set_connection_state(
+    connection_state: &watch::Sender<ConnectionState>,
+    new_state: ConnectionState,
+) {
+    connection_state.send_if_modified(|state| {
+        let modified = *state != new_state;
+        *state = new_state;
+        modified
+    });
+}
@@ -11,6 +11,18 @@ pub enum RealtimeError {
     #[error("Failed to serialize message to JSON: {0}")]
     SerializationError(#[from] serde_json::Error),
 
+    #[error("Failed to decode received frame {frame}: {source}")]
+    MessageDecodeError {
+        frame: String,
+        source: serde_json::Error,
+    },
+
+    #[error("Invalid binary frame: {reason}")]
+    BinaryFrameError { reason: String },
+
+    #[error("Binary broadcasts require ProtocolVersion::V2")]
+    BinaryBroadcastUnsupported,
+
     #[error("WebSocket connection closed unexpectedly")]
     ConnectionClosed,
 
@@ -44,8 +56,14 @@ pub enum RealtimeError {
     #[error("Url must be a valid WebSocket URL or HTTP URL string")]
     InvalidUrlError,
 
+    #[error("Invalid client option '{option}': {reason}")]
+    InvalidClientOption { option: String, reason: String },
+
+    #[error("Timed out connecting to WebSocket")]
+    ConnectTimeout,
+
     #[error(
-        "Tried to subscribe multiple times. 'subscribe' can only be called a single time per channel instance"
+        "Tried to subscribe multiple times. A channel can only be subscribed again once it is closed"
     )]
     MultipleSubscriptionError,
 
@@ -54,6 +72,44 @@ pub enum RealtimeError {
     )]
     PushWhileUnsubscribedError { event: String, topic: String },
 
+    #[error("Failed to get an access token from the token provider: {source}")]
+    TokenProviderError {
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+
+    #[error("Channel {topic} does not belong to this client")]
+    ChannelNotFound { topic: String },
+
+    #[error("The client this channel was created from has been dropped")]
+    ClientDropped,
+
+    #[error(
+        "Mismatch between server and client bindings for postgres changes: requested {requested}, server returned {returned}"
+    )]
+    PostgresChangesMismatch { requested: String, returned: String },
+
+    #[error("Failed to deserialize payload of broadcast '{event}': {source}")]
+    BroadcastDecodeError {
+        event: String,
+        source: serde_json::Error,
+    },
+
+    #[error("Server rejected broadcast '{event}': {reason}")]
+    BroadcastError { event: String, reason: String },
+
+    #[error("The push buffer of {topic} is full ({capacity} pushes), '{event}' was not sent")]
+    PushBufferFull {
+        event: String,
+        topic: String,
+        capacity: usize,
+    },
+
+    #[error("Server rejected the access token on {topic}: {reason}")]
+    AccessTokenRejected { topic: String, reason: String },
+
+    #[error("Timed out waiting for the server to reply to '{event}'")]
+    PushTimeout { event: String },
+
     #[error("Subscribe error: {payload}")]
     SubscribeError { payload: String },
 
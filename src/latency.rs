@@ -0,0 +1,56 @@
+use std::{collections::VecDeque, sync::Mutex, time::Duration};
+
+use tokio::sync::broadcast::{self, Receiver, Sender};
+
+/// Number of heartbeat round trips the moving average is computed over.
+pub const LATENCY_WINDOW: usize = 10;
+
+const LATENCY_CHANNEL_CAPACITY: usize = 16;
+
+/// Heartbeat round-trip times measured by the connection. Shared by the client so the samples
+/// survive reconnections.
+pub(crate) struct HeartbeatLatency {
+    samples: Mutex<VecDeque<Duration>>,
+    sender: Sender<Duration>,
+}
+
+impl HeartbeatLatency {
+    pub(crate) fn new() -> Self {
+        let (sender, _) = broadcast::channel(LATENCY_CHANNEL_CAPACITY);
+
+        Self {
+            samples: Mutex::new(VecDeque::with_capacity(LATENCY_WINDOW)),
+            sender,
+        }
+    }
+
+    pub(crate) fn record(&self, round_trip: Duration) {
+        {
+            let mut samples = self.samples.lock().unwrap();
+            if samples.len() == LATENCY_WINDOW {
+                samples.pop_front();
+            }
+            samples.push_back(round_trip);
+        }
+
+        // there may be nobody listening
+        let _ = self.sender.send(round_trip);
+    }
+
+    pub(crate) fn last(&self) -> Option<Duration> {
+        self.samples.lock().unwrap().back().copied()
+    }
+
+    pub(crate) fn average(&self) -> Option<Duration> {
+        let samples = self.samples.lock().unwrap();
+        if samples.is_empty() {
+            return None;
+        }
+
+        Some(samples.iter().sum::<Duration>() / samples.len() as u32)
+    }
+
+    pub(crate) fn subscribe(&self) -> Receiver<Duration> {
+        self.sender.subscribe()
+    }
+}
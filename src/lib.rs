@@ -1,10 +1,17 @@
+pub mod broadcast_bindings;
 pub mod channel;
 pub mod channel_event;
 pub mod client;
+pub mod client_builder;
 pub mod connection;
 pub mod error;
+pub mod latency;
+pub mod presence;
 pub mod protocol_objects;
 pub mod push;
+pub mod reconnect_policy;
+pub mod serializer;
 pub mod task;
+pub mod token_provider;
 pub mod types;
 pub mod utils;
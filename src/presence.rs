@@ -0,0 +1,314 @@
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    sync::{Arc, Mutex},
+};
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use crate::{
+    protocol_objects::{Payload, Presence, PresenceDiff, PresenceMeta, PresenceState},
+    types::{Logger, Result},
+};
+
+/// Presences currently tracked on a channel, grouped by presence key.
+pub type PresenceMap<T = Value> = HashMap<String, Vec<PresenceMeta<T>>>;
+
+type PresenceChangeCallback = Arc<dyn Fn(&str, &[PresenceMeta], &[PresenceMeta]) + Send + Sync>;
+type PresenceSyncCallback = Arc<dyn Fn() + Send + Sync>;
+
+/// Keeps the presence state of a channel in sync with the server, applying `presence_state` and
+/// `presence_diff` the same way supabase-js does. Cloning it is cheap, and clones share the same
+/// state and callbacks.
+///
+/// The metadata tracked by each client is exposed as `T`, see [`typed`](Self::typed).
+pub struct RealtimePresence<T = Value> {
+    state: Arc<Mutex<PresenceSyncState>>,
+    callbacks: Arc<Mutex<PresenceCallbacks>>,
+    logger: Logger,
+    meta: PhantomData<fn() -> T>,
+}
+
+#[derive(Default)]
+struct PresenceSyncState {
+    presences: PresenceMap,
+    join_ref: Option<String>,
+    pending_diffs: Vec<PresenceDiff>,
+}
+
+#[derive(Clone, Default)]
+struct PresenceCallbacks {
+    on_join: Vec<PresenceChangeCallback>,
+    on_leave: Vec<PresenceChangeCallback>,
+    on_sync: Vec<PresenceSyncCallback>,
+}
+
+/// A join or leave found while syncing, reported to the callbacks once the state is unlocked.
+enum PresenceChange {
+    Join {
+        key: String,
+        current: Vec<PresenceMeta>,
+        joined: Vec<PresenceMeta>,
+    },
+    Leave {
+        key: String,
+        current: Vec<PresenceMeta>,
+        left: Vec<PresenceMeta>,
+    },
+}
+
+impl<T> Clone for RealtimePresence<T> {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+            callbacks: self.callbacks.clone(),
+            logger: self.logger.clone(),
+            meta: PhantomData,
+        }
+    }
+}
+
+impl<T: DeserializeOwned> RealtimePresence<T> {
+    /// Current presences, grouped by presence key.
+    pub fn state(&self) -> Result<PresenceMap<T>> {
+        let state = self.state.lock().unwrap();
+        state
+            .presences
+            .iter()
+            .map(|(key, metas)| Ok((key.clone(), deserialize_metas(metas)?)))
+            .collect()
+    }
+
+    /// Called with the key, the presences already known for it and the ones that joined.
+    pub fn on_join<F>(&self, callback: F)
+    where
+        F: Fn(&str, &[PresenceMeta<T>], &[PresenceMeta<T>]) + Send + Sync + 'static,
+    {
+        self.callbacks
+            .lock()
+            .unwrap()
+            .on_join
+            .push(typed_callback(callback, self.logger.clone()));
+    }
+
+    /// Called with the key, the presences remaining for it and the ones that left.
+    pub fn on_leave<F>(&self, callback: F)
+    where
+        F: Fn(&str, &[PresenceMeta<T>], &[PresenceMeta<T>]) + Send + Sync + 'static,
+    {
+        self.callbacks
+            .lock()
+            .unwrap()
+            .on_leave
+            .push(typed_callback(callback, self.logger.clone()));
+    }
+
+    /// Called every time the state was synced with the server.
+    pub fn on_sync<F>(&self, callback: F)
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.callbacks
+            .lock()
+            .unwrap()
+            .on_sync
+            .push(Arc::new(callback));
+    }
+}
+
+impl RealtimePresence {
+    pub(crate) fn new(logger: Logger) -> Self {
+        Self {
+            state: Arc::default(),
+            callbacks: Arc::default(),
+            logger,
+            meta: PhantomData,
+        }
+    }
+
+    /// View of the same presence state with the tracked metadata deserialized as `T`.
+    pub fn typed<T: DeserializeOwned>(&self) -> RealtimePresence<T> {
+        RealtimePresence {
+            state: self.state.clone(),
+            callbacks: self.callbacks.clone(),
+            logger: self.logger.clone(),
+            meta: PhantomData,
+        }
+    }
+
+    /// Applies a `presence_state` or `presence_diff` received while the channel's join ref was
+    /// `join_ref`. Diffs received before the state of the current join are applied after it.
+    pub(crate) fn on_payload(&self, payload: Payload, join_ref: Option<&str>) {
+        let mut changes = vec![];
+
+        {
+            let mut state = self.state.lock().unwrap();
+            match payload {
+                Payload::PresenceState(new_state) => {
+                    state.join_ref = join_ref.map(String::from);
+                    sync_state(&mut state.presences, new_state, &mut changes);
+                    for diff in std::mem::take(&mut state.pending_diffs) {
+                        sync_diff(&mut state.presences, diff, &mut changes);
+                    }
+                }
+                Payload::PresenceDiff(diff) => {
+                    if state.join_ref.is_none() || state.join_ref.as_deref() != join_ref {
+                        state.pending_diffs.push(diff);
+                        return;
+                    }
+
+                    sync_diff(&mut state.presences, diff, &mut changes);
+                }
+                _ => return,
+            }
+        }
+
+        let callbacks = self.callbacks.lock().unwrap().clone();
+        for change in &changes {
+            match change {
+                PresenceChange::Join {
+                    key,
+                    current,
+                    joined,
+                } => {
+                    for callback in &callbacks.on_join {
+                        callback(key, current, joined);
+                    }
+                }
+                PresenceChange::Leave { key, current, left } => {
+                    for callback in &callbacks.on_leave {
+                        callback(key, current, left);
+                    }
+                }
+            }
+        }
+        for callback in &callbacks.on_sync {
+            callback();
+        }
+    }
+}
+
+/// Replaces `presences` with `new_state`, turning the difference between both into a diff.
+fn sync_state(
+    presences: &mut PresenceMap,
+    new_state: PresenceState,
+    changes: &mut Vec<PresenceChange>,
+) {
+    let mut joins = HashMap::new();
+    let mut leaves = HashMap::new();
+
+    for (key, current) in presences.iter() {
+        if !new_state.0.contains_key(key) {
+            leaves.insert(
+                key.clone(),
+                Presence {
+                    metas: current.clone(),
+                },
+            );
+        }
+    }
+
+    for (key, new_presence) in new_state.0 {
+        let Some(current) = presences.get(&key) else {
+            joins.insert(key, new_presence);
+            continue;
+        };
+
+        let joined: Vec<_> = new_presence
+            .metas
+            .iter()
+            .filter(|meta| !contains_ref(current, &meta.phx_ref))
+            .cloned()
+            .collect();
+        let left: Vec<_> = current
+            .iter()
+            .filter(|meta| !contains_ref(&new_presence.metas, &meta.phx_ref))
+            .cloned()
+            .collect();
+
+        if !joined.is_empty() {
+            joins.insert(key.clone(), Presence { metas: joined });
+        }
+        if !left.is_empty() {
+            leaves.insert(key, Presence { metas: left });
+        }
+    }
+
+    sync_diff(presences, PresenceDiff { joins, leaves }, changes);
+}
+
+fn sync_diff(presences: &mut PresenceMap, diff: PresenceDiff, changes: &mut Vec<PresenceChange>) {
+    for (key, joined) in diff.joins {
+        let current = presences.remove(&key).unwrap_or_default();
+
+        let mut merged: Vec<_> = current
+            .iter()
+            .filter(|meta| !contains_ref(&joined.metas, &meta.phx_ref))
+            .cloned()
+            .collect();
+        merged.extend(joined.metas.iter().cloned());
+        presences.insert(key.clone(), merged);
+
+        changes.push(PresenceChange::Join {
+            key,
+            current,
+            joined: joined.metas,
+        });
+    }
+
+    for (key, left) in diff.leaves {
+        let Some(current) = presences.remove(&key) else {
+            continue;
+        };
+
+        let remaining: Vec<_> = current
+            .into_iter()
+            .filter(|meta| !contains_ref(&left.metas, &meta.phx_ref))
+            .collect();
+        if !remaining.is_empty() {
+            presences.insert(key.clone(), remaining.clone());
+        }
+
+        changes.push(PresenceChange::Leave {
+            key,
+            current: remaining,
+            left: left.metas,
+        });
+    }
+}
+
+fn contains_ref(metas: &[PresenceMeta], phx_ref: &str) -> bool {
+    metas.iter().any(|meta| meta.phx_ref == phx_ref)
+}
+
+fn deserialize_metas<T: DeserializeOwned>(metas: &[PresenceMeta]) -> Result<Vec<PresenceMeta<T>>> {
+    metas
+        .iter()
+        .map(|meta| {
+            Ok(PresenceMeta {
+                phx_ref: meta.phx_ref.clone(),
+                phx_ref_prev: meta.phx_ref_prev.clone(),
+                meta: serde_json::from_value(meta.meta.clone())?,
+            })
+        })
+        .collect()
+}
+
+/// Wraps a callback taking typed metadata. Changes whose metadata doesn't deserialize as `T` are
+/// logged instead of being reported to it.
+fn typed_callback<T, F>(callback: F, logger: Logger) -> PresenceChangeCallback
+where
+    T: DeserializeOwned,
+    F: Fn(&str, &[PresenceMeta<T>], &[PresenceMeta<T>]) + Send + Sync + 'static,
+{
+    Arc::new(move |key, current, changed| {
+        match (deserialize_metas(current), deserialize_metas(changed)) {
+            (Ok(current), Ok(changed)) => callback(key, &current, &changed),
+            (Err(error), _) | (_, Err(error)) => logger(
+                "presence",
+                &format!("Skipping change of {key}, its metadata failed to deserialize: {error}"),
+            ),
+        }
+    })
+}
@@ -1,3 +1,4 @@
+use bytes::Bytes;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -8,3 +9,11 @@ pub struct Broadcast {
     pub event: String,
     pub payload: Value,
 }
+
+/// Broadcast whose payload is sent as raw bytes rather than JSON. Only supported by
+/// [`ProtocolVersion::V2`](crate::types::ProtocolVersion::V2).
+#[derive(Clone, Debug, Default)]
+pub struct BinaryBroadcast {
+    pub event: String,
+    pub payload: Bytes,
+}
@@ -11,4 +11,9 @@ pub struct Message {
     // #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "ref")]
     pub ref_field: Option<String>,
+    /// Ref of the join the message belongs to. Only sent with [`ProtocolVersion::V2`].
+    ///
+    /// [`ProtocolVersion::V2`]: crate::types::ProtocolVersion::V2
+    #[serde(skip)]
+    pub join_ref: Option<String>,
 }
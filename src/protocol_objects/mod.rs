@@ -12,7 +12,8 @@ mod postgres_changes;
 mod presence;
 mod system;
 
-pub use broadcast::Broadcast;
+pub use access_token::AccessToken;
+pub use broadcast::{BinaryBroadcast, Broadcast};
 pub(crate) use heartbeat::Heartbeat;
 pub use message::Message;
 pub use payload::Payload;
@@ -21,3 +22,5 @@ pub use phx_error::*;
 pub use phx_join::*;
 pub use phx_leave::*;
 pub use phx_reply::*;
+pub use postgres_changes::*;
+pub use presence::*;
@@ -1,10 +1,11 @@
 use std::fmt::Display;
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, de::Error};
+use serde_json::Value;
 
 use super::{
     access_token::AccessToken,
-    broadcast::Broadcast,
+    broadcast::{BinaryBroadcast, Broadcast},
     heartbeat::Heartbeat,
     phx_close::PhxClose,
     phx_error::PhxError,
@@ -12,7 +13,7 @@ use super::{
     phx_leave::PhxLeave,
     phx_reply::PhxReply,
     postgres_changes::PostgresChangesPayload,
-    presence::{PresenceDiff, PresenceState},
+    presence::{PresenceDiff, PresencePush, PresenceState},
     system::System,
 };
 
@@ -35,6 +36,11 @@ pub enum Payload {
     AccessToken(AccessToken),
     #[serde(rename = "broadcast")]
     Broadcast(Broadcast),
+    /// Sent and received as a binary frame, see [`BinaryBroadcast`].
+    #[serde(skip)]
+    BinaryBroadcast(BinaryBroadcast),
+    #[serde(rename = "presence")]
+    Presence(PresencePush),
     #[serde(rename = "presence_state")]
     PresenceState(PresenceState),
     #[serde(rename = "presence_diff")]
@@ -43,6 +49,46 @@ pub enum Payload {
     System(System),
     #[serde(rename = "postgres_changes")]
     PostgresChanges(PostgresChangesPayload),
+    /// An event this client doesn't know about, kept as it was received.
+    #[serde(untagged)]
+    Unknown {
+        #[serde(deserialize_with = "deserialize_unknown_event")]
+        event: String,
+        #[serde(default)]
+        payload: Value,
+    },
+}
+
+const KNOWN_EVENTS: [&str; 13] = [
+    "phx_join",
+    "phx_leave",
+    "phx_reply",
+    "phx_close",
+    "phx_error",
+    "heartbeat",
+    "access_token",
+    "broadcast",
+    "presence",
+    "presence_state",
+    "presence_diff",
+    "system",
+    "postgres_changes",
+];
+
+/// Known events whose payload doesn't match their variant must fail to decode, rather than
+/// being received as unknown.
+fn deserialize_unknown_event<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let event = String::deserialize(deserializer)?;
+    if KNOWN_EVENTS.contains(&event.as_str()) {
+        return Err(D::Error::custom(format!(
+            "payload of '{event}' doesn't match the expected format"
+        )));
+    }
+
+    Ok(event)
 }
 
 impl Display for Payload {
@@ -55,11 +101,13 @@ impl Display for Payload {
             Payload::PhxError(_) => write!(f, "phx_error"),
             Payload::Heartbeat(_) => write!(f, "heartbeat"),
             Payload::AccessToken(_) => write!(f, "access_token"),
-            Payload::Broadcast(_) => write!(f, "broadcast"),
+            Payload::Broadcast(_) | Payload::BinaryBroadcast(_) => write!(f, "broadcast"),
+            Payload::Presence(_) => write!(f, "presence"),
             Payload::PresenceState(_) => write!(f, "presence_state"),
             Payload::PresenceDiff(_) => write!(f, "presence_diff"),
             Payload::System(_) => write!(f, "system"),
             Payload::PostgresChanges(_) => write!(f, "postgres_changes"),
+            Payload::Unknown { event, .. } => write!(f, "{event}"),
         }
     }
 }
@@ -1,5 +1,11 @@
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, de::IgnoredAny};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
+#[serde(rename_all = "snake_case", from = "IgnoredAny")]
 pub struct PhxClose;
+
+impl From<IgnoredAny> for PhxClose {
+    fn from(_: IgnoredAny) -> Self {
+        PhxClose
+    }
+}
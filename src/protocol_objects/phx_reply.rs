@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use super::{JoinPostgresChangedEvent, JoinPostgresChanges};
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(tag = "status", content = "response")]
 pub enum PhxReply {
@@ -36,4 +38,37 @@ pub struct ReplyPostgresChanges {
 pub enum ReplyPostgresChangedEvent {
     #[serde(rename = "*")]
     All,
+    #[serde(rename = "INSERT")]
+    Insert,
+    #[serde(rename = "UPDATE")]
+    Update,
+    #[serde(rename = "DELETE")]
+    Delete,
+}
+
+impl ReplyPostgresChanges {
+    /// Whether this is the filter the server registered for `requested`.
+    pub fn matches(&self, requested: &JoinPostgresChanges) -> bool {
+        let same_event = matches!(
+            (&self.event, &requested.event),
+            (
+                ReplyPostgresChangedEvent::All,
+                JoinPostgresChangedEvent::All
+            ) | (
+                ReplyPostgresChangedEvent::Insert,
+                JoinPostgresChangedEvent::Insert
+            ) | (
+                ReplyPostgresChangedEvent::Update,
+                JoinPostgresChangedEvent::Update
+            ) | (
+                ReplyPostgresChangedEvent::Delete,
+                JoinPostgresChangedEvent::Delete
+            )
+        );
+
+        same_event
+            && self.schema == requested.schema
+            && self.table == requested.table
+            && self.filter == requested.filter
+    }
 }
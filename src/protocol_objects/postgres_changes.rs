@@ -1,9 +1,10 @@
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum PostgresDataChangeEvent {
+    #[default]
     Insert,
     Update,
     Delete,
@@ -17,7 +18,7 @@ pub struct Column {
     pub type_: String,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub struct PostgresChangesData {
     pub columns: Vec<Column>,
@@ -35,7 +36,7 @@ pub struct PostgresChangesData {
     pub type_: PostgresDataChangeEvent,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct PostgresChangesPayload {
     pub data: PostgresChangesData,
     pub ids: Vec<i64>,
@@ -1,24 +1,66 @@
 use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
+/// Presences by key, with the metadata each client tracked deserialized as `T`.
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct PresenceState(pub HashMap<String, Presence>);
+pub struct PresenceState<T = Value>(pub HashMap<String, Presence<T>>);
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct Presence {
-    pub metas: Vec<PresenceMeta>,
+pub struct Presence<T = Value> {
+    pub metas: Vec<PresenceMeta<T>>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct PresenceMeta {
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PresenceMeta<T = Value> {
     pub phx_ref: String,
-    pub name: Option<String>,
-    // TODO: t
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phx_ref_prev: Option<String>,
+    /// Metadata passed to `track`.
+    #[serde(flatten)]
+    pub meta: T,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub struct PresenceDiff {
-    pub joins: HashMap<String, Presence>,
-    pub leaves: HashMap<String, Presence>,
+pub struct PresenceDiff<T = Value> {
+    pub joins: HashMap<String, Presence<T>>,
+    pub leaves: HashMap<String, Presence<T>>,
+}
+
+/// Starts or stops tracking the client's presence on a channel.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PresencePush {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub event: PresencePushEvent,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload: Option<Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PresencePushEvent {
+    Track,
+    Untrack,
+}
+
+impl PresencePush {
+    pub fn track(meta: Value) -> Self {
+        Self {
+            type_: String::from("presence"),
+            event: PresencePushEvent::Track,
+            payload: Some(meta),
+        }
+    }
+
+    pub fn untrack() -> Self {
+        Self {
+            type_: String::from("presence"),
+            event: PresencePushEvent::Untrack,
+            payload: None,
+        }
+    }
 }
@@ -1,14 +1,24 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll, ready},
+    time::Duration,
+};
 
 use tokio::{
-    sync::{Mutex, oneshot::Receiver},
+    sync::{
+        Mutex,
+        oneshot::{self, Receiver},
+    },
     task::AbortHandle,
 };
 
 use crate::{
     client::RealtimeClient,
-    protocol_objects::{Message, Payload},
-    types::{DEFAULT_TIMEOUT, Hook, PayloadResponse, PushCallback, PushReplyStatus, Result},
+    protocol_objects::{Message, Payload, PhxReply, PhxResponse},
+    types::{
+        DEFAULT_TIMEOUT, Hook, Logger, PayloadResponse, PushCallback, PushReplyStatus, Result,
+    },
 };
 
 #[derive(Clone)]
@@ -21,11 +31,18 @@ pub struct Push {
     received_response: Arc<Mutex<Option<PayloadResponse>>>,
     rec_hooks: Arc<Mutex<Vec<Hook>>>,
     ref_event: Option<String>,
-    timeout_abort_handle: Option<AbortHandle>, // TODO: make JoinHandle
+    /// Shared by the clones of the push, so that any of them can stop waiting for the reply.
+    timeout_abort_handle: Arc<std::sync::Mutex<Option<AbortHandle>>>, // TODO: make JoinHandle
+    logger: Logger,
 }
 
 impl Push {
-    pub(crate) fn new(event: &str, payload: Payload, timeout: Option<Duration>) -> Self {
+    pub(crate) fn new(
+        event: &str,
+        payload: Payload,
+        timeout: Option<Duration>,
+        logger: Logger,
+    ) -> Self {
         Self {
             event: String::from(event),
             payload,
@@ -34,8 +51,9 @@ impl Push {
             ref_event: None,
             received_response: Arc::new(Mutex::new(None)),
             is_sent: false,
-            timeout_abort_handle: None,
+            timeout_abort_handle: Arc::default(),
             rec_hooks: Arc::new(Mutex::new(vec![])),
+            logger,
         }
     }
 
@@ -43,23 +61,32 @@ impl Push {
         &mut self,
         client: &RealtimeClient,
         topic: &str,
+        join_ref: Option<&str>,
         _ref: &str,
         current_event: &str,
         receiver: Receiver<PayloadResponse>,
     ) -> Result<()> {
+        self.reset();
+        self.send(client, topic, join_ref, _ref, current_event, receiver)
+            .await
+    }
+
+    /// Forgets the previous send of this push and its reply.
+    pub(crate) fn reset(&mut self) {
         // self.cancel_ref_event(channel);
+        self.cancel_timeout();
         self._ref = None;
         self.ref_event = None;
         self.received_response = Arc::new(Mutex::new(None));
         self.is_sent = false;
-        self.send(client, topic, _ref, current_event, receiver)
-            .await
     }
 
+    /// Sends the push as part of the channel join `join_ref`.
     pub(crate) async fn send(
         &mut self,
         client: &RealtimeClient,
         topic: &str,
+        join_ref: Option<&str>,
         _ref: &str,
         current_event: &str,
         receiver: Receiver<PayloadResponse>,
@@ -75,12 +102,20 @@ impl Push {
             topic: String::from(topic),
             payload: self.payload.clone(),
             ref_field: self._ref.clone(),
+            join_ref: join_ref.map(String::from),
         };
 
         client.send(message)
     }
 
-    fn update_payload(&mut self, payload: Payload) {
+    /// Stops waiting for the reply to the last send, without calling any hook.
+    pub(crate) fn cancel_timeout(&self) {
+        if let Some(timeout_abort_handle) = self.timeout_abort_handle.lock().unwrap().take() {
+            timeout_abort_handle.abort();
+        }
+    }
+
+    pub(crate) fn update_payload(&mut self, payload: Payload) {
         // TODO: update without overwriting
         self.payload = payload;
     }
@@ -100,6 +135,41 @@ impl Push {
         rec_hooks_mutex.push(Hook::new(status, callback));
     }
 
+    /// Resolves with the first reply to this push, be it ok, an error or a timeout.
+    pub(crate) async fn wait_for_reply(&mut self) -> oneshot::Receiver<PayloadResponse> {
+        let (sender, receiver) = oneshot::channel();
+        let sender = Arc::new(std::sync::Mutex::new(Some(sender)));
+
+        for status in [
+            PushReplyStatus::Ok,
+            PushReplyStatus::Error,
+            PushReplyStatus::TimedOut,
+        ] {
+            let sender = sender.clone();
+            let callback = move |payload: &Payload| {
+                if let Some(sender) = sender.lock().unwrap().take() {
+                    let _ = sender.send(PayloadResponse::new(status, payload.clone()));
+                }
+            };
+            self.register_receive_callback(status, Box::new(callback))
+                .await;
+        }
+
+        receiver
+    }
+
+    /// Times out the push without it having been sent.
+    pub(crate) async fn expire(&self) {
+        let payload_response =
+            PayloadResponse::new(PushReplyStatus::TimedOut, self.payload.clone());
+        Self::on_reply(
+            payload_response,
+            self.received_response.clone(),
+            self.rec_hooks.clone(),
+        )
+        .await;
+    }
+
     async fn on_reply(
         payload_response: PayloadResponse,
         received_response: Arc<Mutex<Option<PayloadResponse>>>,
@@ -123,7 +193,8 @@ impl Push {
         current_event: &str,
         receiver: Receiver<PayloadResponse>,
     ) {
-        if self.timeout_abort_handle.is_some() {
+        let mut timeout_abort_handle = self.timeout_abort_handle.lock().unwrap();
+        if timeout_abort_handle.is_some() {
             return;
         }
 
@@ -132,8 +203,10 @@ impl Push {
 
         let timeout = self.timeout;
         let event = self.event.clone();
+        let payload = self.payload.clone();
         let received_response = self.received_response.clone();
         let rec_hooks = self.rec_hooks.clone();
+        let logger = self.logger.clone();
 
         let timeout_handle = tokio::spawn(async move {
             let timeout_result = tokio::time::timeout(timeout, receiver).await;
@@ -143,17 +216,28 @@ impl Push {
                         Self::on_reply(payload_response, received_response, rec_hooks).await;
                     }
                     Err(error) => {
-                        eprintln!("Receiver error in push for event: {event}. Error: {error}.");
+                        // the connection was lost, so the reply will never arrive
+                        logger(
+                            "push",
+                            &format!("Receiver error in push for event: {event}. Error: {error}."),
+                        );
+                        let payload_response =
+                            PayloadResponse::new(PushReplyStatus::TimedOut, payload);
+                        Self::on_reply(payload_response, received_response, rec_hooks).await;
                     }
                 },
                 Err(elapsed) => {
-                    println!("Timeout occurred after {elapsed} in push for event: {event}");
-                    // TODO: trigger timeout event on channel
+                    logger(
+                        "push",
+                        &format!("Timeout occurred after {elapsed} in push for event: {event}"),
+                    );
+                    let payload_response = PayloadResponse::new(PushReplyStatus::TimedOut, payload);
+                    Self::on_reply(payload_response, received_response, rec_hooks).await;
                 }
             }
         });
 
-        self.timeout_abort_handle = Some(timeout_handle.abort_handle());
+        *timeout_abort_handle = Some(timeout_handle.abort_handle());
     }
 
     pub(crate) async fn clear_event_callbacks(&mut self) {
@@ -168,8 +252,52 @@ impl Push {
             false
         }
     }
+}
+
+/// Reply of the server to a push.
+#[derive(Clone, Debug)]
+pub enum PushReply {
+    Ok(PhxResponse),
+    /// The reason given by the server.
+    Error(String),
+    /// No reply arrived within the push's timeout, or the connection was lost before it did.
+    TimedOut,
+}
+
+/// Resolves with the [`PushReply`] to a channel push.
+pub struct PushHandle {
+    receiver: Receiver<PayloadResponse>,
+}
+
+impl PushHandle {
+    pub(crate) fn new(receiver: Receiver<PayloadResponse>) -> Self {
+        Self { receiver }
+    }
+}
+
+impl Future for PushHandle {
+    type Output = PushReply;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let Ok(payload_response) = ready!(Pin::new(&mut self.receiver).poll(cx)) else {
+            return Poll::Ready(PushReply::TimedOut);
+        };
+
+        let reply = match (
+            payload_response.get_status(),
+            payload_response.get_payload(),
+        ) {
+            (PushReplyStatus::Ok, Payload::PhxReply(PhxReply::Ok(response))) => {
+                PushReply::Ok(response.clone())
+            }
+            (PushReplyStatus::Ok, _) => PushReply::Ok(PhxResponse::default()),
+            (PushReplyStatus::Error, Payload::PhxReply(PhxReply::Error(error))) => {
+                PushReply::Error(error.reason.clone())
+            }
+            (PushReplyStatus::Error, payload) => PushReply::Error(payload.to_string()),
+            (PushReplyStatus::TimedOut, _) => PushReply::TimedOut,
+        };
 
-    pub(crate) fn get_ref(&self) -> &Option<String> {
-        &self._ref
+        Poll::Ready(reply)
     }
 }
@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::types::{DEFAULT_INITIAL_BACKOFF, DEFAULT_MAX_BACKOFF, DEFAULT_MAX_RETRIES};
+
+/// Decides how long to wait before retrying a failed connection attempt.
+pub trait ReconnectPolicy: Send + Sync {
+    /// Delay before retry number `attempt` (starting at 1), or `None` to give up.
+    fn next_delay(&self, attempt: u32) -> Option<Duration>;
+}
+
+/// Exponential backoff with full jitter: retry `n` waits a random duration between zero and
+/// `min(max, initial * 2^(n - 1))`, so that clients disconnected together don't retry together.
+#[derive(Debug, Clone)]
+pub struct ExponentialBackoff {
+    initial: Duration,
+    max: Duration,
+    max_retries: Option<u32>,
+}
+
+impl ExponentialBackoff {
+    /// Retries forever unless limited with [`with_max_retries`](Self::with_max_retries).
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            max_retries: None,
+        }
+    }
+
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = Some(max_retries);
+        self
+    }
+
+    /// Upper bound of the delay before retry number `attempt`.
+    pub fn max_delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial
+            .checked_mul(factor)
+            .unwrap_or(self.max)
+            .min(self.max)
+    }
+}
+
+impl Default for ExponentialBackoff {
+    fn default() -> Self {
+        Self::new(DEFAULT_INITIAL_BACKOFF, DEFAULT_MAX_BACKOFF)
+            .with_max_retries(DEFAULT_MAX_RETRIES)
+    }
+}
+
+impl ReconnectPolicy for ExponentialBackoff {
+    fn next_delay(&self, attempt: u32) -> Option<Duration> {
+        if self
+            .max_retries
+            .is_some_and(|max_retries| attempt > max_retries)
+        {
+            return None;
+        }
+
+        let max_delay = self.max_delay(attempt).as_secs_f64();
+        let delay = rand::thread_rng().gen_range(0.0..=max_delay);
+
+        Some(Duration::from_secs_f64(delay))
+    }
+}
+
+/// Waits the same delay before every retry.
+#[derive(Debug, Clone)]
+pub struct FixedDelay {
+    delay: Duration,
+    max_retries: Option<u32>,
+}
+
+impl FixedDelay {
+    /// Retries forever unless limited with [`with_max_retries`](Self::with_max_retries).
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            max_retries: None,
+        }
+    }
+
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = Some(max_retries);
+        self
+    }
+}
+
+impl ReconnectPolicy for FixedDelay {
+    fn next_delay(&self, attempt: u32) -> Option<Duration> {
+        if self
+            .max_retries
+            .is_some_and(|max_retries| attempt > max_retries)
+        {
+            return None;
+        }
+
+        Some(self.delay)
+    }
+}
+
+/// Never retries.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NeverRetry;
+
+impl ReconnectPolicy for NeverRetry {
+    fn next_delay(&self, _attempt: u32) -> Option<Duration> {
+        None
+    }
+}
@@ -0,0 +1,180 @@
+use bytes::Bytes;
+use serde_json::{Value, json};
+use tokio_tungstenite::tungstenite::Message as TMessage;
+
+use crate::{
+    error::RealtimeError,
+    protocol_objects::{BinaryBroadcast, Broadcast, Message, Payload},
+    types::{ProtocolVersion, Result},
+};
+
+/// Kind of a binary frame, its first byte.
+const USER_BROADCAST_PUSH: u8 = 3;
+const USER_BROADCAST: u8 = 4;
+
+/// How the payload of a binary user broadcast is encoded.
+const BINARY_ENCODING: u8 = 0;
+const JSON_ENCODING: u8 = 1;
+
+/// Encodes `message` as a frame of the given serializer version.
+///
+/// V1 frames are objects (`{"topic", "event", "payload", "ref"}`), V2 frames are arrays
+/// (`[join_ref, ref, topic, event, payload]`), or binary frames for binary broadcasts.
+pub fn encode(message: &Message, vsn: ProtocolVersion) -> Result<TMessage> {
+    match (vsn, &message.payload) {
+        (ProtocolVersion::V1, Payload::BinaryBroadcast(_)) => {
+            Err(RealtimeError::BinaryBroadcastUnsupported)
+        }
+        (ProtocolVersion::V1, _) => Ok(TMessage::Text(serde_json::to_string(message)?)),
+        (ProtocolVersion::V2, Payload::BinaryBroadcast(broadcast)) => Ok(TMessage::Binary(
+            encode_user_broadcast_push(message, broadcast)?,
+        )),
+        (ProtocolVersion::V2, _) => {
+            // the payload serializes as `{"event": ..., "payload": ...}`
+            let mut payload = serde_json::to_value(&message.payload)?;
+            let event = payload["event"].take();
+            let payload = payload["payload"].take();
+
+            Ok(TMessage::Text(serde_json::to_string(&json!([
+                message.join_ref,
+                message.ref_field,
+                message.topic,
+                event,
+                payload
+            ]))?))
+        }
+    }
+}
+
+/// Decodes a text frame of either serializer version, telling them apart by their shape.
+pub fn decode(text: &str) -> Result<Message> {
+    let decode_error = |source| RealtimeError::MessageDecodeError {
+        frame: String::from(text),
+        source,
+    };
+
+    if !text.trim_start().starts_with('[') {
+        return serde_json::from_str(text).map_err(decode_error);
+    }
+
+    let (join_ref, _ref, topic, event, payload): (
+        Option<String>,
+        Option<String>,
+        String,
+        String,
+        Value,
+    ) = serde_json::from_str(text).map_err(decode_error)?;
+
+    let mut message: Message = serde_json::from_value(json!({
+        "topic": topic,
+        "event": event,
+        "payload": payload,
+        "ref": _ref,
+    }))
+    .map_err(decode_error)?;
+    message.join_ref = join_ref;
+
+    Ok(message)
+}
+
+/// Decodes a binary frame of the V2 serializer. Only user broadcasts are sent as binary frames
+/// by the server.
+pub fn decode_binary(frame: Bytes) -> Result<Message> {
+    let mut reader = FrameReader { frame, offset: 0 };
+
+    let kind = reader.byte()?;
+    if kind != USER_BROADCAST {
+        return Err(frame_error(&format!("unsupported kind {kind}")));
+    }
+
+    let topic_size = reader.byte()?;
+    let event_size = reader.byte()?;
+    let metadata_size = reader.byte()?;
+    let encoding = reader.byte()?;
+
+    let topic = reader.string(topic_size)?;
+    let event = reader.string(event_size)?;
+    // metadata only holds what the sender asked to expose, which this client never does
+    reader.bytes(metadata_size as usize)?;
+    let payload = reader.rest();
+
+    let payload = match encoding {
+        BINARY_ENCODING => Payload::BinaryBroadcast(BinaryBroadcast { event, payload }),
+        JSON_ENCODING => Payload::Broadcast(Broadcast {
+            event,
+            payload: serde_json::from_slice(&payload)
+                .map_err(|error| frame_error(&format!("invalid JSON payload: {error}")))?,
+        }),
+        _ => return Err(frame_error(&format!("unsupported encoding {encoding}"))),
+    };
+
+    Ok(Message {
+        topic,
+        payload,
+        ref_field: None,
+        join_ref: None,
+    })
+}
+
+fn encode_user_broadcast_push(message: &Message, broadcast: &BinaryBroadcast) -> Result<Vec<u8>> {
+    let join_ref = message.join_ref.as_deref().unwrap_or_default();
+    let _ref = message.ref_field.as_deref().unwrap_or_default();
+    let fields = [join_ref, _ref, &message.topic, &broadcast.event];
+
+    let mut frame = Vec::with_capacity(
+        7 + fields.iter().map(|field| field.len()).sum::<usize>() + broadcast.payload.len(),
+    );
+    frame.push(USER_BROADCAST_PUSH);
+    for field in fields {
+        let size = u8::try_from(field.len())
+            .map_err(|_| frame_error(&format!("'{field}' is longer than 255 bytes")))?;
+        frame.push(size);
+    }
+    // no metadata
+    frame.push(0);
+    frame.push(BINARY_ENCODING);
+    for field in fields {
+        frame.extend_from_slice(field.as_bytes());
+    }
+    frame.extend_from_slice(&broadcast.payload);
+
+    Ok(frame)
+}
+
+struct FrameReader {
+    frame: Bytes,
+    offset: usize,
+}
+
+impl FrameReader {
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn bytes(&mut self, size: usize) -> Result<Bytes> {
+        if self.offset + size > self.frame.len() {
+            return Err(frame_error("frame is shorter than its header"));
+        }
+
+        let bytes = self.frame.slice(self.offset..self.offset + size);
+        self.offset += size;
+        Ok(bytes)
+    }
+
+    fn string(&mut self, size: u8) -> Result<String> {
+        let bytes = self.bytes(size as usize)?;
+        String::from_utf8(bytes.to_vec()).map_err(|error| frame_error(&error.to_string()))
+    }
+
+    fn rest(&mut self) -> Bytes {
+        let rest = self.frame.slice(self.offset..);
+        self.offset = self.frame.len();
+        rest
+    }
+}
+
+fn frame_error(reason: &str) -> RealtimeError {
+    RealtimeError::BinaryFrameError {
+        reason: String::from(reason),
+    }
+}
@@ -24,8 +24,10 @@ where
         let cancellation_token_clone = cancellation_token.clone();
         let handle = tokio::spawn(async move {
             tokio::select! {
+                biased;
+
+                _ = cancellation_token_clone.cancelled() => Err(Cancelled),
                 result = future => Ok(result),
-                _ = cancellation_token_clone.cancelled() => Err(Cancelled)
             }
         });
 
@@ -35,7 +37,7 @@ where
         }
     }
 
-    pub async fn cancel(&self) {
+    pub fn cancel(&self) {
         self.cancellation_token.cancel();
     }
 }
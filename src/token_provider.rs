@@ -0,0 +1,59 @@
+use std::{
+    error::Error,
+    pin::Pin,
+    time::{Duration, SystemTime},
+};
+
+use data_encoding::BASE64URL_NOPAD;
+use serde::Deserialize;
+
+/// Future returned by [`TokenProvider::token`].
+pub type TokenFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<String, Box<dyn Error + Send + Sync>>> + Send + 'a>>;
+
+/// Supplies the access token sent when joining channels, e.g. a user JWT from your auth service.
+///
+/// The client asks for a token before connecting, and again ahead of the `exp` claim of the
+/// current one, sending the new token to every joined channel. Closures returning a future
+/// implement it:
+///
+/// ```no_run
+/// # use supabase_realtime_rs::client::RealtimeClient;
+/// # async fn fetch_user_jwt() -> Result<String, std::io::Error> { unimplemented!() }
+/// # fn main() -> supabase_realtime_rs::types::Result<()> {
+/// let client = RealtimeClient::builder("http://127.0.0.1:54321", "api-key")
+///     .token_provider(|| async { Ok(fetch_user_jwt().await?) })
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait TokenProvider: Send + Sync {
+    fn token(&self) -> TokenFuture<'_>;
+}
+
+impl<F, Fut> TokenProvider for F
+where
+    F: Fn() -> Fut + Send + Sync,
+    Fut: Future<Output = Result<String, Box<dyn Error + Send + Sync>>> + Send + 'static,
+{
+    fn token(&self) -> TokenFuture<'_> {
+        Box::pin(self())
+    }
+}
+
+#[derive(Deserialize)]
+struct Claims {
+    exp: Option<u64>,
+}
+
+/// When the JWT `token` expires, according to its `exp` claim. The token is decoded without
+/// verifying its signature. `None` if it isn't a JWT or has no `exp` claim.
+pub fn expires_at(token: &str) -> Option<SystemTime> {
+    let claims = token.split('.').nth(1)?;
+    let claims = BASE64URL_NOPAD
+        .decode(claims.trim_end_matches('=').as_bytes())
+        .ok()?;
+    let claims: Claims = serde_json::from_slice(&claims).ok()?;
+
+    SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(claims.exp?))
+}
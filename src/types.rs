@@ -1,20 +1,32 @@
-use std::{pin::Pin, time::Duration};
+use std::{fmt::Display, pin::Pin, sync::Arc, time::Duration};
 
 use crate::{
     channel::RealtimeChannelMutableState,
     channel_event::ChannelEvent,
     error::RealtimeError,
-    protocol_objects::{Message, Payload},
+    protocol_objects::{JoinPostgresChanges, Message, Payload},
 };
 
 // Constants
 pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
 pub const DEFAULT_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(25);
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DEFAULT_MAX_RETRIES: u32 = 5;
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(60);
+pub const DEFAULT_REJOIN_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+pub const DEFAULT_REJOIN_MAX_BACKOFF: Duration = Duration::from_secs(10);
+pub const DEFAULT_PUSH_BUFFER_CAPACITY: usize = 100;
+pub const DEFAULT_PUSH_BUFFER_TTL: Duration = Duration::from_secs(10);
+pub const DEFAULT_TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(60);
+/// Delay before asking the token provider again after it failed, or returned a token that
+/// expires within the refresh margin.
+pub const TOKEN_RETRY_DELAY: Duration = Duration::from_secs(5);
 
 pub type Result<Type> = std::result::Result<Type, RealtimeError>;
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
-pub(crate) enum ChannelState {
+pub enum ChannelState {
     Joined,
     #[default]
     Closed,
@@ -23,11 +35,51 @@ pub(crate) enum ChannelState {
     Leaving,
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connecting,
+    Open,
+    Closing,
+    #[default]
+    Closed,
+    /// The connection was lost and is being re-established. `attempt` is 0 until the first
+    /// retry.
+    Reconnecting {
+        attempt: u32,
+    },
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SubscribeState {
     Subscribed,
     TimedOut,
     Closed,
+    /// The server reported an error on the channel. It will be rejoined, or closed once the
+    /// client's rejoin policy gives up.
+    ChannelError,
+    /// `phx_join` is being sent again after the channel errored or timed out.
+    Rejoining {
+        attempt: u32,
+    },
+}
+
+/// Version of the Phoenix serializer spoken over the socket, sent as the `vsn` URL parameter.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ProtocolVersion {
+    #[default]
+    V1,
+    /// Array frames carrying the join ref of the channel, used to discard messages from a
+    /// previous join.
+    V2,
+}
+
+impl Display for ProtocolVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolVersion::V1 => write!(f, "1.0.0"),
+            ProtocolVersion::V2 => write!(f, "2.0.0"),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -37,10 +89,16 @@ pub(crate) enum PushReplyStatus {
     Error,
 }
 
+/// Receives the kind of a log entry (e.g. `transport`) and its message.
+pub type Logger = Arc<dyn Fn(&str, &str) + Send + Sync>;
+
+/// Receives every decoded message, or the error of a frame that couldn't be decoded.
 pub(crate) type ConnectionMessageReceivedEvent =
-    Box<dyn Fn(Message) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+    Box<dyn Fn(Result<Message>) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
 pub(crate) type SubscribeCallback = Box<dyn Fn(Result<SubscribeState>) + Send + Sync>;
 pub(crate) type PushCallback = Box<dyn Fn(&Payload) + Send + Sync>;
+pub(crate) type BroadcastErrorCallback = Arc<dyn Fn(&RealtimeError) + Send + Sync>;
+pub(crate) type DecodeErrorCallback = Arc<dyn Fn(&RealtimeError) + Send + Sync>;
 
 pub(crate) struct Binding {
     callback: ChannelEvent,
@@ -62,6 +120,22 @@ impl Binding {
         self.callback
             .invoke(channel_state, payload, _ref, should_remove_channel)
     }
+
+    pub(crate) fn postgres_changes_filter(&self) -> Option<&JoinPostgresChanges> {
+        self.callback.postgres_changes_filter()
+    }
+
+    pub(crate) fn set_id(&mut self, id: i64) {
+        self.id = Some(id);
+    }
+
+    /// Postgres changes are only delivered to the bindings whose server-assigned id they list.
+    pub(crate) fn accepts(&self, payload: &Payload) -> bool {
+        match payload {
+            Payload::PostgresChanges(change) => self.id.is_some_and(|id| change.ids.contains(&id)),
+            _ => true,
+        }
+    }
 }
 
 pub(crate) struct Hook {
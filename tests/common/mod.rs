@@ -0,0 +1,166 @@
+#![allow(dead_code)]
+
+use std::{
+    collections::HashSet,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
+
+use futures::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use tokio::{net::TcpListener, sync::mpsc};
+use tokio_tungstenite::tungstenite::Message as TMessage;
+
+/// Accepts connections one after the other, recording every received frame, replying to
+/// `access_token` with an error and to every other push with `ok`, and echoing broadcasts. Frames
+/// can also be pushed to the client with [`TestServer::send`], and joins replied to with
+/// [`TestServer::reply`] once [held](TestServer::hold_replies).
+pub struct TestServer {
+    pub url: String,
+    received: mpsc::UnboundedReceiver<Value>,
+    commands: mpsc::UnboundedSender<Command>,
+    connections: Arc<AtomicUsize>,
+    held_replies: Arc<Mutex<HashSet<String>>>,
+}
+
+enum Command {
+    Send(Value),
+    /// Drops the socket without a close frame, as if the connection was lost.
+    Drop,
+}
+
+impl TestServer {
+    pub async fn spawn() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (received_sender, received) = mpsc::unbounded_channel();
+        let (commands, mut command_receiver) = mpsc::unbounded_channel();
+        let connections = Arc::new(AtomicUsize::new(0));
+        let held_replies = Arc::new(Mutex::new(HashSet::new()));
+
+        let accepted = connections.clone();
+        let held = held_replies.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let Ok(mut ws) = tokio_tungstenite::accept_async(stream).await else {
+                    continue;
+                };
+                accepted.fetch_add(1, Ordering::SeqCst);
+
+                loop {
+                    tokio::select! {
+                        frame = ws.next() => {
+                            let Some(Ok(TMessage::Text(text))) = frame else {
+                                break;
+                            };
+                            let frame: Value = serde_json::from_str(&text).unwrap();
+                            let event = frame["event"].as_str().unwrap_or_default();
+                            let held = held.lock().unwrap().contains(event);
+                            for reply in replies(&frame).into_iter().filter(|_| !held) {
+                                let _ = ws.send(TMessage::Text(reply.to_string())).await;
+                            }
+                            let _ = received_sender.send(frame);
+                        }
+                        Some(command) = command_receiver.recv() => match command {
+                            Command::Send(frame) => {
+                                let _ = ws.send(TMessage::Text(frame.to_string())).await;
+                            }
+                            Command::Drop => break,
+                        }
+                    }
+                }
+            }
+        });
+
+        Self {
+            url,
+            received,
+            commands,
+            connections,
+            held_replies,
+        }
+    }
+
+    /// Number of connections accepted so far.
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+
+    /// Stops replying to pushes of `event` (e.g. `phx_join` or `heartbeat`), leaving it to the
+    /// test.
+    pub fn hold_replies(&self, event: &str) {
+        self.held_replies
+            .lock()
+            .unwrap()
+            .insert(String::from(event));
+    }
+
+    /// Replies to `frame` with `status` and `response`.
+    pub fn reply(&self, frame: &Value, status: &str, response: Value) {
+        let reply = json!({
+            "topic": frame["topic"],
+            "event": "phx_reply",
+            "payload": {"status": status, "response": response},
+            "ref": frame["ref"],
+        });
+        self.commands.send(Command::Send(reply)).unwrap();
+    }
+
+    /// Drops the current connection without closing it.
+    pub fn drop_connection(&self) {
+        self.commands.send(Command::Drop).unwrap();
+    }
+
+    /// Waits for the client to send `event`, skipping the frames before it.
+    pub async fn next_event(&mut self, event: &str) -> Value {
+        self.frames_until(event).await.pop().unwrap()
+    }
+
+    /// Waits for the client to send `event`, returning every frame received up to it.
+    pub async fn frames_until(&mut self, event: &str) -> Vec<Value> {
+        let mut frames = vec![];
+        loop {
+            let frame = self.received.recv().await.expect("Server stopped.");
+            let found = frame["event"] == event;
+            frames.push(frame);
+            if found {
+                return frames;
+            }
+        }
+    }
+
+    /// Sends `event` to the client, on the channel with the given (full) topic.
+    pub fn send(&self, topic: &str, event: &str, payload: Value) {
+        let frame = json!({
+            "topic": topic,
+            "event": event,
+            "payload": payload,
+            "ref": null,
+        });
+        self.commands.send(Command::Send(frame)).unwrap();
+    }
+}
+
+fn replies(frame: &Value) -> Vec<Value> {
+    let reply = match frame["event"].as_str() {
+        Some("access_token") => json!({"status": "error", "response": {"reason": "invalid token"}}),
+        _ => json!({"status": "ok", "response": {}}),
+    };
+    let mut replies = vec![json!({
+        "topic": frame["topic"],
+        "event": "phx_reply",
+        "payload": reply,
+        "ref": frame["ref"],
+    })];
+    if frame["event"] == "broadcast" {
+        replies.push(json!({
+            "topic": frame["topic"],
+            "event": "broadcast",
+            "payload": frame["payload"],
+            "ref": null,
+        }));
+    }
+    replies
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use serde_json::json;
+use tokio::sync::mpsc;
+
+use supabase_realtime_rs::{
+    client::RealtimeClient, protocol_objects::Payload, types::ChannelState,
+};
+
+use common::TestServer;
+
+const KEY: &str = "api-key";
+
+/// Broadcasts each of `events` to a channel listening to `pattern`, returning the events that
+/// reached the binding.
+async fn dispatched(pattern: &str, events: &[&str]) -> Vec<String> {
+    let server = TestServer::spawn().await;
+    let client = RealtimeClient::builder(&server.url, KEY)
+        .logger(|_, _| {})
+        .build()
+        .expect("Error while building client.");
+    let mut channel = client.create_channel("test-broadcast-pattern", None).await;
+
+    let (sender, mut received) = mpsc::unbounded_channel();
+    let matched = sender.clone();
+    channel
+        .on_broadcast(pattern, move |payload| {
+            if let Payload::Broadcast(broadcast) = payload {
+                let _ = matched.send(Some(broadcast.event));
+            }
+        })
+        .await;
+    // sent last, so that every event before it has been dispatched once it arrives
+    channel
+        .on_broadcast("done", move |_| {
+            let _ = sender.send(None);
+        })
+        .await;
+
+    let mut state = channel.state_watch();
+    channel.subscribe(None).await.unwrap();
+    state
+        .wait_for(|state| *state == ChannelState::Joined)
+        .await
+        .unwrap();
+
+    for event in events.iter().chain(&["done"]) {
+        server.send(
+            "realtime:test-broadcast-pattern",
+            "broadcast",
+            json!({"event": event, "payload": {}}),
+        );
+    }
+
+    let mut events = vec![];
+    while let Some(event) = received.recv().await.expect("Channel dropped.") {
+        events.push(event);
+    }
+    events.retain(|event| event != "done");
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_wildcard_matches_every_event() {
+        let events = dispatched("*", &["cursor.move", ""]).await;
+        assert_eq!(events, ["cursor.move", ""]);
+    }
+
+    #[tokio::test]
+    async fn test_prefix_pattern() {
+        let events = dispatched(
+            "cursor.*",
+            &["cursor.move", "cursor.", "cursor", "message.cursor.move"],
+        )
+        .await;
+        assert_eq!(events, ["cursor.move", "cursor."]);
+    }
+
+    #[tokio::test]
+    async fn test_multiple_wildcards_backtrack() {
+        // in "abxbcc", the first `b` doesn't lead to a match, a later one does
+        let events = dispatched("a*b*c", &["abc", "axxbyyc", "abxbcc", "axxbyy", "xabc"]).await;
+        assert_eq!(events, ["abc", "axxbyyc", "abxbcc"]);
+    }
+
+    #[tokio::test]
+    async fn test_pattern_without_wildcard_is_exact() {
+        let events = dispatched("foo", &["foo", "foobar"]).await;
+        assert_eq!(events, ["foo"]);
+    }
+}
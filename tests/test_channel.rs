@@ -0,0 +1,264 @@
+mod common;
+
+use std::{sync::Arc, time::Duration};
+
+use serde_json::json;
+use tokio::sync::mpsc;
+
+use supabase_realtime_rs::{
+    client::RealtimeClient,
+    error::RealtimeError,
+    reconnect_policy::{FixedDelay, NeverRetry},
+    types::{ChannelState, Result, SubscribeState},
+};
+
+use common::TestServer;
+
+const KEY: &str = "api-key";
+
+/// Forwards every state reported to the subscribe callback.
+fn subscribe_states() -> (
+    Box<dyn Fn(Result<SubscribeState>) + Send + Sync>,
+    mpsc::UnboundedReceiver<Result<SubscribeState>>,
+) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    let callback = Box::new(move |state| {
+        let _ = sender.send(state);
+    });
+    (callback, receiver)
+}
+
+async fn next_state(
+    states: &mut mpsc::UnboundedReceiver<Result<SubscribeState>>,
+) -> SubscribeState {
+    states
+        .recv()
+        .await
+        .expect("Channel dropped.")
+        .expect("Subscription failed.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_concurrent_subscribes_join_once() {
+        let mut server = TestServer::spawn().await;
+        let client = RealtimeClient::builder(&server.url, KEY)
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let channel = client
+            .create_channel("test-concurrent-subscribe", None)
+            .await;
+
+        // keeps the client locked while the broadcast is received, so that the first subscribe
+        // waits for it after checking the channel
+        let mut busy = client.create_channel("test-busy", None).await;
+        busy.on_broadcast("busy", |_| std::thread::sleep(Duration::from_millis(300)))
+            .await;
+        let mut busy_state = busy.state_watch();
+        busy.subscribe(None).await.unwrap();
+        busy_state
+            .wait_for(|state| *state == ChannelState::Joined)
+            .await
+            .unwrap();
+        busy.broadcast("busy", &json!({})).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let (mut first, mut second) = (channel.clone(), channel.clone());
+        let (first, second) = tokio::join!(first.subscribe(None), second.subscribe(None));
+        let results = [first, second];
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 1);
+        assert!(
+            results
+                .iter()
+                .any(|result| matches!(result, Err(RealtimeError::MultipleSubscriptionError)))
+        );
+
+        let mut state = channel.state_watch();
+        state
+            .wait_for(|state| *state == ChannelState::Joined)
+            .await
+            .unwrap();
+        channel.broadcast("joined", &json!({})).await.unwrap();
+
+        // sent by `busy`
+        server.next_event("broadcast").await;
+        let frames = server.frames_until("broadcast").await;
+        let joins = frames
+            .iter()
+            .filter(|frame| frame["event"] == "phx_join")
+            .count();
+        assert_eq!(joins, 1);
+    }
+
+    #[tokio::test]
+    async fn test_errored_channel_is_rejoined() {
+        let mut server = TestServer::spawn().await;
+        let client = RealtimeClient::builder(&server.url, KEY)
+            .rejoin_policy(FixedDelay::new(Duration::from_millis(50)))
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let mut channel = client.create_channel("test-rejoin-errored", None).await;
+
+        let (callback, mut states) = subscribe_states();
+        channel.subscribe(Some(callback)).await.unwrap();
+        let first_join = server.next_event("phx_join").await;
+        assert_eq!(next_state(&mut states).await, SubscribeState::Subscribed);
+
+        server.send("realtime:test-rejoin-errored", "phx_error", json!({}));
+        assert_eq!(next_state(&mut states).await, SubscribeState::ChannelError);
+        assert_eq!(*channel.state_watch().borrow(), ChannelState::Errored);
+
+        assert_eq!(
+            next_state(&mut states).await,
+            SubscribeState::Rejoining { attempt: 1 }
+        );
+        let second_join = server.next_event("phx_join").await;
+        assert_ne!(second_join["ref"], first_join["ref"]);
+        assert_eq!(next_state(&mut states).await, SubscribeState::Subscribed);
+        assert_eq!(*channel.state_watch().borrow(), ChannelState::Joined);
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_join_is_retried() {
+        let mut server = TestServer::spawn().await;
+        server.hold_replies("phx_join");
+        let client = RealtimeClient::builder(&server.url, KEY)
+            .timeout(Duration::from_millis(100))
+            .rejoin_policy(FixedDelay::new(Duration::from_millis(50)))
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let mut channel = client.create_channel("test-rejoin-timed-out", None).await;
+
+        let (callback, mut states) = subscribe_states();
+        channel.subscribe(Some(callback)).await.unwrap();
+        server.next_event("phx_join").await;
+        assert_eq!(next_state(&mut states).await, SubscribeState::TimedOut);
+        assert_eq!(
+            next_state(&mut states).await,
+            SubscribeState::Rejoining { attempt: 1 }
+        );
+
+        let join = server.next_event("phx_join").await;
+        server.reply(&join, "ok", json!({}));
+        assert_eq!(next_state(&mut states).await, SubscribeState::Subscribed);
+        assert_eq!(*channel.state_watch().borrow(), ChannelState::Joined);
+    }
+
+    #[tokio::test]
+    async fn test_removed_channel_is_dropped() {
+        let server = TestServer::spawn().await;
+        let client = RealtimeClient::builder(&server.url, KEY)
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let mut channel = client.create_channel("test-channel-dropped", None).await;
+
+        // held by the channel's presence callbacks, so only freed along with the channel
+        let token = Arc::new(());
+        let dropped = Arc::downgrade(&token);
+        channel.presence().on_sync(move || {
+            let _token = &token;
+        });
+
+        let mut state = channel.state_watch();
+        channel.subscribe(None).await.unwrap();
+        state
+            .wait_for(|state| *state == ChannelState::Joined)
+            .await
+            .unwrap();
+
+        client.remove_channel(&channel).await.unwrap();
+        drop(channel);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert!(dropped.upgrade().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_channel_closes_when_rejoining_gives_up() {
+        let server = TestServer::spawn().await;
+        let client = RealtimeClient::builder(&server.url, KEY)
+            .rejoin_policy(NeverRetry)
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let mut channel = client.create_channel("test-rejoin-gives-up", None).await;
+
+        let (callback, mut states) = subscribe_states();
+        channel.subscribe(Some(callback)).await.unwrap();
+        assert_eq!(next_state(&mut states).await, SubscribeState::Subscribed);
+
+        server.send("realtime:test-rejoin-gives-up", "phx_error", json!({}));
+        assert_eq!(next_state(&mut states).await, SubscribeState::ChannelError);
+        assert_eq!(next_state(&mut states).await, SubscribeState::Closed);
+        assert_eq!(*channel.state_watch().borrow(), ChannelState::Closed);
+
+        let (callback, mut states) = subscribe_states();
+        channel.subscribe(Some(callback)).await.unwrap();
+        assert_eq!(next_state(&mut states).await, SubscribeState::Subscribed);
+    }
+
+    #[tokio::test]
+    async fn test_push_timeout_is_logged() {
+        let server = TestServer::spawn().await;
+        server.hold_replies("broadcast");
+        let (sender, mut logs) = mpsc::unbounded_channel();
+        let client = RealtimeClient::builder(&server.url, KEY)
+            .timeout(Duration::from_millis(100))
+            .logger(move |kind, message| {
+                let _ = sender.send((String::from(kind), String::from(message)));
+            })
+            .build()
+            .expect("Error while building client.");
+        let mut channel = client.create_channel("test-push-timeout", None).await;
+        let mut state = channel.state_watch();
+        channel.subscribe(None).await.unwrap();
+        state
+            .wait_for(|state| *state == ChannelState::Joined)
+            .await
+            .unwrap();
+
+        channel
+            .broadcast("unacknowledged", &json!({}))
+            .await
+            .unwrap();
+        loop {
+            let (kind, message) = logs.recv().await.expect("Client dropped.");
+            if kind == "push" {
+                assert!(message.contains("Timeout occurred"));
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribing_closed_channel_does_nothing() {
+        let mut server = TestServer::spawn().await;
+        let client = RealtimeClient::builder(&server.url, KEY)
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let mut channel = client.create_channel("test-unsubscribe-closed", None).await;
+
+        let (callback, mut states) = subscribe_states();
+        channel.subscribe(Some(callback)).await.unwrap();
+        assert_eq!(next_state(&mut states).await, SubscribeState::Subscribed);
+        assert!(channel.unsubscribe().await.unwrap().is_some());
+        assert_eq!(next_state(&mut states).await, SubscribeState::Closed);
+        server.next_event("phx_leave").await;
+
+        let mut state = channel.state_watch();
+        state.mark_unchanged();
+        assert!(channel.unsubscribe().await.unwrap().is_none());
+        assert!(!state.has_changed().unwrap());
+        assert_eq!(*state.borrow(), ChannelState::Closed);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(states.try_recv().is_err());
+    }
+}
@@ -0,0 +1,169 @@
+mod common;
+
+use serde_json::json;
+use tokio::sync::mpsc;
+
+use supabase_realtime_rs::{
+    client::RealtimeClient,
+    error::RealtimeError,
+    protocol_objects::{Broadcast, Payload},
+    types::ChannelState,
+};
+
+use common::TestServer;
+
+const URL: &str = "http://127.0.0.1:54321";
+const KEY: &str = "api-key";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_channel_does_not_keep_client_alive() {
+        let client = RealtimeClient::builder(URL, KEY)
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let channel = client.create_channel("test-client-dropped", None).await;
+
+        drop(client);
+
+        let payload = Payload::Broadcast(Broadcast {
+            event: String::from("test-event"),
+            payload: json!({}),
+        });
+        let result = channel.send_broadcast("test-event", payload).await;
+        assert!(matches!(result, Err(RealtimeError::ClientDropped)));
+    }
+
+    #[tokio::test]
+    async fn test_remove_channel() {
+        let client = RealtimeClient::builder(URL, KEY)
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let channel = client.create_channel("test-remove-channel", None).await;
+
+        // never joined, so it is removed without waiting for the server
+        let result = client.remove_channel(&channel).await;
+        assert!(matches!(result, Ok(None)));
+        assert_eq!(*channel.state_watch().borrow(), ChannelState::Closed);
+
+        let result = client.remove_channel(&channel).await;
+        assert!(
+            matches!(result, Err(RealtimeError::ChannelNotFound { topic }) if topic == "realtime:test-remove-channel")
+        );
+        assert!(client.remove_all_channels().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_set_auth_skips_channels_not_joined() {
+        let client = RealtimeClient::builder(URL, KEY)
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        client.create_channel("test-set-auth", None).await;
+
+        assert!(client.set_auth("user-token").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_set_auth_token_is_used_to_join() {
+        let mut server = TestServer::spawn().await;
+        let client = RealtimeClient::builder(&server.url, KEY)
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let mut channel = client.create_channel("test-set-auth-join", None).await;
+
+        assert!(client.set_auth("user-token").await.is_empty());
+        channel.subscribe(None).await.unwrap();
+
+        let join = server.next_event("phx_join").await;
+        assert_eq!(join["payload"]["access_token"], "user-token");
+    }
+
+    #[tokio::test]
+    async fn test_set_auth_reports_rejected_token() {
+        let mut server = TestServer::spawn().await;
+        let client = RealtimeClient::builder(&server.url, KEY)
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let mut channel = client.create_channel("test-set-auth-rejected", None).await;
+        let mut state = channel.state_watch();
+        channel.subscribe(None).await.unwrap();
+        state
+            .wait_for(|state| *state == ChannelState::Joined)
+            .await
+            .unwrap();
+
+        let failures = client.set_auth("expired-token").await;
+        assert!(matches!(
+            failures.get("realtime:test-set-auth-rejected"),
+            Some(RealtimeError::AccessTokenRejected { reason, .. }) if reason == "invalid token"
+        ));
+
+        let push = server.next_event("access_token").await;
+        assert_eq!(push["payload"]["access_token"], "expired-token");
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_event_is_matched_exactly() {
+        let server = TestServer::spawn().await;
+        let client = RealtimeClient::builder(&server.url, KEY)
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let mut channel = client.create_channel("test-broadcast-events", None).await;
+
+        let (exact_sender, mut exact) = mpsc::unbounded_channel();
+        channel
+            .on_broadcast("foo", move |payload| {
+                let _ = exact_sender.send(payload);
+            })
+            .await;
+        let (pattern_sender, mut pattern) = mpsc::unbounded_channel();
+        channel
+            .on_broadcast("foo*", move |payload| {
+                let _ = pattern_sender.send(payload);
+            })
+            .await;
+
+        let mut state = channel.state_watch();
+        channel.subscribe(None).await.unwrap();
+        state
+            .wait_for(|state| *state == ChannelState::Joined)
+            .await
+            .unwrap();
+
+        for event in ["foobar", "foo"] {
+            channel.broadcast(event, &json!({})).await.unwrap();
+        }
+
+        let received = |payload: Option<Payload>| match payload {
+            Some(Payload::Broadcast(broadcast)) => broadcast.event,
+            _ => panic!("Expected a broadcast."),
+        };
+        assert_eq!(received(pattern.recv().await), "foobar");
+        assert_eq!(received(pattern.recv().await), "foo");
+        // sent after `foobar`, so `foobar` would have been received first
+        assert_eq!(received(exact.recv().await), "foo");
+    }
+
+    #[tokio::test]
+    async fn test_binary_broadcast_requires_v2() {
+        let client = RealtimeClient::builder(URL, KEY)
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let channel = client.create_channel("test-binary-broadcast", None).await;
+
+        let result = channel.broadcast_binary("test-event", vec![1, 2, 3]).await;
+        assert!(matches!(
+            result,
+            Err(RealtimeError::BinaryBroadcastUnsupported)
+        ));
+    }
+}
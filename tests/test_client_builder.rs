@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+use supabase_realtime_rs::{
+    client::RealtimeClient,
+    error::RealtimeError,
+    types::{ConnectionState, ProtocolVersion},
+};
+
+const URL: &str = "http://127.0.0.1:54321";
+const KEY: &str = "api-key";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_with_options() {
+        let client = RealtimeClient::builder(URL, KEY)
+            .heartbeat_interval(Duration::from_secs(5))
+            .timeout(Duration::from_secs(3))
+            .connect_timeout(Duration::from_secs(2))
+            .push_buffer_capacity(10)
+            .push_buffer_ttl(Duration::from_secs(5))
+            .param("log_level", "info")
+            .vsn(ProtocolVersion::V2)
+            .header("x-client-info", "supabase-realtime-rs")
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+
+        assert!(!client.is_connected());
+        assert_eq!(*client.connection_state().borrow(), ConnectionState::Closed);
+    }
+
+    #[test]
+    fn test_builder_rejects_invalid_options() {
+        let result = RealtimeClient::builder("127.0.0.1:54321", KEY).build();
+        assert!(matches!(result, Err(RealtimeError::InvalidUrlError)));
+
+        let result = RealtimeClient::builder(URL, KEY)
+            .heartbeat_interval(Duration::ZERO)
+            .build();
+        assert!(
+            matches!(result, Err(RealtimeError::InvalidClientOption { option, .. }) if option == "heartbeat_interval")
+        );
+
+        let result = RealtimeClient::builder(URL, KEY)
+            .push_buffer_ttl(Duration::ZERO)
+            .build();
+        assert!(
+            matches!(result, Err(RealtimeError::InvalidClientOption { option, .. }) if option == "push_buffer_ttl")
+        );
+
+        let result = RealtimeClient::builder(URL, KEY)
+            .header("bad header", "value")
+            .build();
+        assert!(
+            matches!(result, Err(RealtimeError::InvalidClientOption { option, .. }) if option == "header")
+        );
+
+        let result = RealtimeClient::builder(URL, KEY)
+            .param("vsn", "2.0.0")
+            .build();
+        assert!(
+            matches!(result, Err(RealtimeError::InvalidClientOption { option, .. }) if option == "param")
+        );
+    }
+}
@@ -10,7 +10,7 @@ use tokio::time::timeout;
 use supabase_realtime_rs::{
     client::RealtimeClient,
     protocol_objects::{Broadcast, BroadcastConfig, JoinConfig, Payload, PresenceConfig},
-    types::{Result, SubscribeState},
+    types::{ChannelState, Result, SubscribeState},
 };
 
 const DEFAULT_URL: &str = "http://127.0.0.1:54321";
@@ -46,7 +46,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_broadcast_events() {
-        let mut client = create_client().expect("Error while creating client.");
+        let client = create_client().expect("Error while creating client.");
         client
             .connect()
             .await
@@ -87,7 +87,7 @@ mod tests {
             .await;
 
         channel
-            .subscribe(&mut client, Some(Box::new(subscribe_callback)))
+            .subscribe(Some(Box::new(subscribe_callback)))
             .await
             .expect("Error while subscribing to channel.");
 
@@ -102,7 +102,7 @@ mod tests {
             });
 
             channel
-                .send_broadcast(&mut client, "test-event", payload)
+                .send_broadcast("test-event", payload)
                 .await
                 .expect("Error while sending broadcast.");
 
@@ -134,7 +134,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_delayed_single_broadcast() {
-        let mut client = create_client().expect("Error while creating client.");
+        let client = create_client().expect("Error while creating client.");
         client.connect().await.unwrap();
         assert!(client.is_connected());
 
@@ -172,7 +172,7 @@ mod tests {
             .await;
 
         channel
-            .subscribe(&mut client, Some(Box::new(subscribe_callback)))
+            .subscribe(Some(Box::new(subscribe_callback)))
             .await
             .expect("Error while subscribing to channel.");
 
@@ -189,7 +189,7 @@ mod tests {
         });
 
         channel
-            .send_broadcast(&mut client, "test-event", payload)
+            .send_broadcast("test-event", payload)
             .await
             .expect("Error while sending broadcast.");
 
@@ -217,4 +217,122 @@ mod tests {
 
         client.close().await.expect("Error disconnecting client.");
     }
+
+    #[tokio::test]
+    async fn test_heartbeat_latency() {
+        let client = create_client().expect("Error while creating client.");
+        client.connect().await.unwrap();
+        assert!(client.latency().is_none());
+
+        let mut latency_receiver = client.subscribe_latency();
+
+        // The first heartbeat is sent one heartbeat interval after connecting
+        let round_trip = timeout(Duration::from_secs(30), latency_receiver.recv())
+            .await
+            .expect("Timeout elapsed while waiting for heartbeat reply.")
+            .expect("Latency channel closed.");
+
+        assert_eq!(client.latency(), Some(round_trip));
+        assert_eq!(client.average_latency(), Some(round_trip));
+
+        client.close().await.expect("Error disconnecting client.");
+    }
+
+    #[tokio::test]
+    async fn test_channel_state_watch() {
+        let client = create_client().expect("Error while creating client.");
+        client.connect().await.unwrap();
+
+        let mut channel = client
+            .create_channel("test-channel-state", Some(BROADCAST_JOIN_CONFIG))
+            .await;
+        let mut state_watch = channel.state_watch();
+        assert_eq!(*state_watch.borrow(), ChannelState::Closed);
+
+        channel.subscribe(None).await.unwrap();
+
+        timeout(
+            Duration::from_secs(10),
+            state_watch.wait_for(|state| *state == ChannelState::Joined),
+        )
+        .await
+        .expect("Timeout elapsed while waiting for the channel to be joined.")
+        .expect("Channel state watch closed.");
+
+        client.close().await.expect("Error disconnecting client.");
+    }
+
+    #[tokio::test]
+    async fn test_resubscribe_after_unsubscribe() {
+        let client = create_client().expect("Error while creating client.");
+        client.connect().await.unwrap();
+
+        let mut channel = client
+            .create_channel("test-resubscribe", Some(BROADCAST_JOIN_CONFIG))
+            .await;
+        let mut state_watch = channel.state_watch();
+
+        for _ in 0..2 {
+            channel.subscribe(None).await.unwrap();
+            timeout(
+                Duration::from_secs(10),
+                state_watch.wait_for(|state| *state == ChannelState::Joined),
+            )
+            .await
+            .expect("Timeout elapsed while waiting for the channel to be joined.")
+            .expect("Channel state watch closed.");
+
+            channel.unsubscribe().await.unwrap();
+            assert_eq!(*state_watch.borrow(), ChannelState::Closed);
+        }
+
+        client.close().await.expect("Error disconnecting client.");
+    }
+
+    #[tokio::test]
+    async fn test_presence_track() {
+        let client = create_client().expect("Error while creating client.");
+        client.connect().await.unwrap();
+
+        let config = JoinConfig {
+            presence: Some(PresenceConfig {
+                key: String::from("test-user"),
+            }),
+            ..BROADCAST_JOIN_CONFIG
+        };
+        let mut channel = client.create_channel("test-presence", Some(config)).await;
+
+        let join_notify = Arc::new(Notify::new());
+        let notify_clone = Arc::clone(&join_notify);
+        channel.presence().on_join(move |key, _, _| {
+            if key == "test-user" {
+                notify_clone.notify_one();
+            }
+        });
+
+        let mut state_watch = channel.state_watch();
+        channel.subscribe(None).await.unwrap();
+        timeout(
+            Duration::from_secs(5),
+            state_watch.wait_for(|state| *state == ChannelState::Joined),
+        )
+        .await
+        .expect("Timeout elapsed while waiting for the channel to be joined.")
+        .unwrap();
+
+        channel
+            .track(&json!({"name": "test"}))
+            .await
+            .expect("Error while tracking presence.");
+
+        timeout(Duration::from_secs(5), join_notify.notified())
+            .await
+            .expect("Timeout elapsed while waiting for presence join.");
+
+        let presences = channel.presence().state().unwrap();
+        assert_eq!(presences["test-user"].len(), 1);
+        assert_eq!(presences["test-user"][0].meta["name"], "test");
+
+        client.close().await.expect("Error disconnecting client.");
+    }
 }
@@ -0,0 +1,54 @@
+use serde_json::json;
+
+use supabase_realtime_rs::protocol_objects::{Message, Payload};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unknown_event_is_kept() {
+        let frame = json!({
+            "topic": "realtime:room",
+            "event": "new_server_event",
+            "payload": {"value": 1},
+            "ref": null
+        });
+
+        let message: Message = serde_json::from_value(frame.clone()).unwrap();
+        let Payload::Unknown { event, payload } = &message.payload else {
+            panic!("Expected an unknown payload, got {}.", message.payload);
+        };
+        assert_eq!(event, "new_server_event");
+        assert_eq!(payload, &json!({"value": 1}));
+
+        assert_eq!(serde_json::to_value(&message).unwrap(), frame);
+    }
+
+    #[test]
+    fn test_lifecycle_event_payload_is_ignored() {
+        for payload in [json!({}), json!(null), json!({"reason": "shutdown"})] {
+            let frame = json!({
+                "topic": "realtime:room",
+                "event": "phx_close",
+                "payload": payload,
+                "ref": "1"
+            });
+
+            let message: Message = serde_json::from_value(frame).unwrap();
+            assert!(matches!(message.payload, Payload::PhxClose(_)));
+        }
+    }
+
+    #[test]
+    fn test_malformed_known_event_fails() {
+        let frame = json!({
+            "topic": "realtime:room",
+            "event": "phx_reply",
+            "payload": {"status": "ok", "response": {"postgres_changes": "not a list"}},
+            "ref": "1"
+        });
+
+        assert!(serde_json::from_value::<Message>(frame).is_err());
+    }
+}
@@ -0,0 +1,182 @@
+mod common;
+
+use serde_json::{Value, json};
+use tokio::sync::mpsc;
+
+use supabase_realtime_rs::{
+    client::RealtimeClient,
+    error::RealtimeError,
+    protocol_objects::{JoinPostgresChangedEvent, JoinPostgresChanges, Payload, PhxReply},
+    types::ChannelState,
+};
+
+use common::TestServer;
+
+const KEY: &str = "api-key";
+
+/// An insert into `public.table`, delivered to the bindings with the given ids.
+fn insert(ids: &[i64], table: &str) -> Value {
+    json!({
+        "ids": ids,
+        "data": {
+            "columns": [{"name": "id", "type": "int8"}],
+            "commit_timestamp": "2024-01-01T00:00:00Z",
+            "errors": null,
+            "record": {"id": 1},
+            "schema": "public",
+            "table": table,
+            "type": "INSERT",
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reply_matches_requested_filters() {
+        let message = json!({
+            "event": "phx_reply",
+            "payload": {
+                "status": "ok",
+                "response": {
+                    "postgres_changes": [
+                        {"event": "INSERT", "schema": "public", "table": "todos", "id": 12},
+                        {"event": "*", "schema": "public", "table": "users", "filter": "id=eq.1", "id": 13}
+                    ]
+                }
+            }
+        });
+
+        let Payload::PhxReply(PhxReply::Ok(response)) = serde_json::from_value(message).unwrap()
+        else {
+            panic!("Expected an ok phx_reply payload.");
+        };
+
+        let todos = JoinPostgresChanges {
+            event: JoinPostgresChangedEvent::Insert,
+            schema: String::from("public"),
+            table: String::from("todos"),
+            filter: None,
+        };
+        let users = JoinPostgresChanges {
+            event: JoinPostgresChangedEvent::All,
+            schema: String::from("public"),
+            table: String::from("users"),
+            filter: Some(String::from("id=eq.1")),
+        };
+
+        assert!(response.postgres_changes[0].matches(&todos));
+        assert!(response.postgres_changes[1].matches(&users));
+        assert!(!response.postgres_changes[0].matches(&users));
+
+        let unfiltered_users = JoinPostgresChanges {
+            filter: None,
+            ..users
+        };
+        assert!(!response.postgres_changes[1].matches(&unfiltered_users));
+    }
+
+    #[tokio::test]
+    async fn test_changes_reach_the_bindings_with_their_id() {
+        let mut server = TestServer::spawn().await;
+        server.hold_replies("phx_join");
+        let client = RealtimeClient::builder(&server.url, KEY)
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let mut channel = client.create_channel("test-postgres-changes", None).await;
+
+        let (sender, mut received) = mpsc::unbounded_channel();
+        for table in ["todos", "users"] {
+            let sender = sender.clone();
+            channel
+                .on_postgres_changes(
+                    JoinPostgresChangedEvent::Insert,
+                    "public",
+                    table,
+                    None,
+                    move |_| {
+                        let _ = sender.send(table);
+                    },
+                )
+                .await;
+        }
+
+        let mut state = channel.state_watch();
+        channel.subscribe(None).await.unwrap();
+        let join = server.next_event("phx_join").await;
+        server.reply(
+            &join,
+            "ok",
+            json!({"postgres_changes": [
+                {"id": 12, "event": "INSERT", "schema": "public", "table": "todos"},
+                {"id": 13, "event": "INSERT", "schema": "public", "table": "users"},
+            ]}),
+        );
+        state
+            .wait_for(|state| *state == ChannelState::Joined)
+            .await
+            .unwrap();
+
+        let topic = "realtime:test-postgres-changes";
+        server.send(topic, "postgres_changes", insert(&[13], "users"));
+        server.send(topic, "postgres_changes", insert(&[12], "todos"));
+        server.send(topic, "postgres_changes", insert(&[99], "todos"));
+        server.send(topic, "postgres_changes", insert(&[12, 13], "todos"));
+
+        let mut tables = vec![];
+        for _ in 0..4 {
+            tables.push(received.recv().await.unwrap());
+        }
+        assert_eq!(tables, ["users", "todos", "todos", "users"]);
+        assert!(received.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_filter_mismatch_fails_the_subscription() {
+        let mut server = TestServer::spawn().await;
+        server.hold_replies("phx_join");
+        let client = RealtimeClient::builder(&server.url, KEY)
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let mut channel = client.create_channel("test-postgres-mismatch", None).await;
+        channel
+            .on_postgres_changes(
+                JoinPostgresChangedEvent::Insert,
+                "public",
+                "todos",
+                Some("id=eq.1"),
+                |_| {},
+            )
+            .await;
+
+        let (sender, mut states) = mpsc::unbounded_channel();
+        let callback = Box::new(move |state| {
+            let _ = sender.send(state);
+        });
+        channel.subscribe(Some(callback)).await.unwrap();
+        let join = server.next_event("phx_join").await;
+        server.reply(
+            &join,
+            "ok",
+            json!({"postgres_changes": [
+                {"id": 12, "event": "INSERT", "schema": "public", "table": "todos", "filter": "id=eq.2"},
+            ]}),
+        );
+
+        let state = states.recv().await.unwrap();
+        assert!(matches!(
+            state,
+            Err(RealtimeError::PostgresChangesMismatch { .. })
+        ));
+        server.next_event("phx_leave").await;
+        let mut state = channel.state_watch();
+        state
+            .wait_for(|state| *state == ChannelState::Closed)
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,235 @@
+mod common;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::sync::mpsc;
+
+use supabase_realtime_rs::{
+    channel::RealtimeChannel,
+    client::RealtimeClient,
+    protocol_objects::{Payload, PresenceMeta, PresenceState},
+    types::ChannelState,
+};
+
+use common::TestServer;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct User {
+    name: String,
+    avatar: String,
+}
+
+/// A presence change reported to a callback: the key, the refs of the current presences and the
+/// refs of the ones that joined or left.
+#[derive(Debug, PartialEq)]
+enum Change {
+    Join(String, Vec<String>, Vec<String>),
+    Leave(String, Vec<String>, Vec<String>),
+}
+
+fn refs(metas: &[PresenceMeta]) -> Vec<String> {
+    metas.iter().map(|meta| meta.phx_ref.clone()).collect()
+}
+
+/// Joins a channel on `server`, forwarding the presence changes and syncs (as `None`).
+async fn join_channel(
+    server: &TestServer,
+    topic: &str,
+) -> (
+    RealtimeClient,
+    RealtimeChannel,
+    mpsc::UnboundedReceiver<Option<Change>>,
+) {
+    let client = RealtimeClient::builder(&server.url, "api-key")
+        .logger(|_, _| {})
+        .build()
+        .expect("Error while building client.");
+    let mut channel = client.create_channel(topic, None).await;
+
+    let (sender, changes) = mpsc::unbounded_channel();
+    let join_sender = sender.clone();
+    channel.presence().on_join(move |key, current, joined| {
+        let change = Change::Join(key.to_string(), refs(current), refs(joined));
+        let _ = join_sender.send(Some(change));
+    });
+    let leave_sender = sender.clone();
+    channel.presence().on_leave(move |key, current, left| {
+        let change = Change::Leave(key.to_string(), refs(current), refs(left));
+        let _ = leave_sender.send(Some(change));
+    });
+    channel.presence().on_sync(move || {
+        let _ = sender.send(None);
+    });
+
+    let mut state = channel.state_watch();
+    channel.subscribe(None).await.unwrap();
+    state
+        .wait_for(|state| *state == ChannelState::Joined)
+        .await
+        .unwrap();
+
+    (client, channel, changes)
+}
+
+/// Changes reported until the next sync, sorted by key.
+async fn next_sync(changes: &mut mpsc::UnboundedReceiver<Option<Change>>) -> Vec<Change> {
+    let mut synced = vec![];
+    while let Some(change) = changes.recv().await.expect("Channel dropped.") {
+        synced.push(change);
+    }
+    synced.sort_by_key(|change| match change {
+        Change::Join(key, ..) | Change::Leave(key, ..) => key.clone(),
+    });
+    synced
+}
+
+fn metas(refs: &[&str]) -> Value {
+    let metas: Vec<_> = refs.iter().map(|r| json!({"phx_ref": r})).collect();
+    json!({"metas": metas})
+}
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presence_meta_round_trip() {
+        let payload = json!({
+            "user-1": {
+                "metas": [{"phx_ref": "F1", "name": "alice", "avatar": "a.png", "color": "red"}]
+            }
+        });
+
+        let state: PresenceState = serde_json::from_value(payload.clone()).unwrap();
+        assert_eq!(state.0["user-1"].metas[0].meta["color"], "red");
+        assert_eq!(serde_json::to_value(&state).unwrap(), payload);
+
+        let state: PresenceState<User> = serde_json::from_value(payload).unwrap();
+        assert_eq!(
+            state.0["user-1"].metas[0],
+            PresenceMeta {
+                phx_ref: String::from("F1"),
+                phx_ref_prev: None,
+                meta: User {
+                    name: String::from("alice"),
+                    avatar: String::from("a.png"),
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_presence_diff_payload() {
+        let message = json!({
+            "event": "presence_diff",
+            "payload": {
+                "joins": {"user-1": {"metas": [{"phx_ref": "F2", "phx_ref_prev": "F1", "name": "bob"}]}},
+                "leaves": {}
+            }
+        });
+
+        let Payload::PresenceDiff(diff) = serde_json::from_value(message).unwrap() else {
+            panic!("Expected a presence_diff payload.");
+        };
+        let meta = &diff.joins["user-1"].metas[0];
+        assert_eq!(meta.phx_ref_prev.as_deref(), Some("F1"));
+        assert_eq!(meta.meta, json!({"name": "bob"}));
+    }
+
+    #[tokio::test]
+    async fn test_presence_state_is_synced_as_diff() {
+        let server = TestServer::spawn().await;
+        let (_client, channel, mut changes) = join_channel(&server, "test-presence-state").await;
+        let topic = "realtime:test-presence-state";
+
+        server.send(
+            topic,
+            "presence_state",
+            json!({"a": metas(&["A1"]), "b": metas(&["B1"])}),
+        );
+        assert_eq!(
+            next_sync(&mut changes).await,
+            vec![
+                Change::Join("a".into(), vec![], strings(&["A1"])),
+                Change::Join("b".into(), vec![], strings(&["B1"])),
+            ]
+        );
+
+        // a new state only reports what changed since the previous one
+        server.send(topic, "presence_state", json!({"a": metas(&["A1", "A2"])}));
+        assert_eq!(
+            next_sync(&mut changes).await,
+            vec![
+                Change::Join("a".into(), strings(&["A1"]), strings(&["A2"])),
+                Change::Leave("b".into(), vec![], strings(&["B1"])),
+            ]
+        );
+
+        let state = channel.presence().state().unwrap();
+        assert_eq!(state.len(), 1);
+        assert_eq!(refs(&state["a"]), strings(&["A1", "A2"]));
+    }
+
+    #[tokio::test]
+    async fn test_presence_diff_before_state_is_applied_after_it() {
+        let server = TestServer::spawn().await;
+        let (_client, channel, mut changes) = join_channel(&server, "test-presence-pending").await;
+        let topic = "realtime:test-presence-pending";
+
+        server.send(
+            topic,
+            "presence_diff",
+            json!({"joins": {"c": metas(&["C1"])}, "leaves": {}}),
+        );
+        server.send(topic, "presence_state", json!({"a": metas(&["A1"])}));
+
+        // the diff doesn't sync on its own, it is applied along with the state
+        assert_eq!(
+            next_sync(&mut changes).await,
+            vec![
+                Change::Join("a".into(), vec![], strings(&["A1"])),
+                Change::Join("c".into(), vec![], strings(&["C1"])),
+            ]
+        );
+
+        let state = channel.presence().state().unwrap();
+        assert_eq!(state.len(), 2);
+        assert_eq!(refs(&state["a"]), strings(&["A1"]));
+        assert_eq!(refs(&state["c"]), strings(&["C1"]));
+    }
+
+    #[tokio::test]
+    async fn test_presence_leave_removes_empty_key() {
+        let server = TestServer::spawn().await;
+        let (_client, channel, mut changes) = join_channel(&server, "test-presence-leave").await;
+        let topic = "realtime:test-presence-leave";
+
+        server.send(
+            topic,
+            "presence_state",
+            json!({"a": metas(&["A1", "A2"]), "b": metas(&["B1"])}),
+        );
+        next_sync(&mut changes).await;
+
+        server.send(
+            topic,
+            "presence_diff",
+            json!({"joins": {}, "leaves": {"a": metas(&["A1"]), "b": metas(&["B1"])}}),
+        );
+        assert_eq!(
+            next_sync(&mut changes).await,
+            vec![
+                Change::Leave("a".into(), strings(&["A2"]), strings(&["A1"])),
+                Change::Leave("b".into(), vec![], strings(&["B1"])),
+            ]
+        );
+
+        let state = channel.presence().state().unwrap();
+        assert_eq!(state.len(), 1);
+        assert_eq!(refs(&state["a"]), strings(&["A2"]));
+    }
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use std::time::Duration;
+
+use serde_json::{Value, json};
+
+use supabase_realtime_rs::{
+    channel::RealtimeChannel,
+    client::RealtimeClient,
+    error::RealtimeError,
+    protocol_objects::{Broadcast, Payload},
+    push::PushReply,
+    types::ChannelState,
+};
+
+use common::TestServer;
+
+const KEY: &str = "api-key";
+
+fn broadcast(event: &str) -> Payload {
+    Payload::Broadcast(Broadcast {
+        event: String::from(event),
+        payload: json!({}),
+    })
+}
+
+/// Events of the broadcasts among `frames`, in the order they were received.
+fn broadcast_events(frames: &[Value]) -> Vec<&str> {
+    frames
+        .iter()
+        .filter(|frame| frame["event"] == "broadcast")
+        .filter_map(|frame| frame["payload"]["event"].as_str())
+        .collect()
+}
+
+/// Subscribes a channel whose join is left unanswered by `server`, returning the join.
+async fn joining_channel(
+    server: &mut TestServer,
+    client: &RealtimeClient,
+    topic: &str,
+) -> (RealtimeChannel, Value) {
+    server.hold_replies("phx_join");
+    let mut channel = client.create_channel(topic, None).await;
+    channel.subscribe(None).await.unwrap();
+    let join = server.next_event("phx_join").await;
+    assert_eq!(*channel.state_watch().borrow(), ChannelState::Joining);
+
+    (channel, join)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_buffered_pushes_are_sent_in_order_once_joined() {
+        let mut server = TestServer::spawn().await;
+        let client = RealtimeClient::builder(&server.url, KEY)
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let (channel, join) = joining_channel(&mut server, &client, "test-buffer-order").await;
+
+        for event in ["first", "second", "third"] {
+            channel.send(event, broadcast(event)).await.unwrap();
+        }
+        let sent_early =
+            tokio::time::timeout(Duration::from_millis(100), server.next_event("broadcast")).await;
+        assert!(sent_early.is_err(), "Pushes were sent before joining.");
+
+        server.reply(&join, "ok", json!({}));
+        let mut frames = server.frames_until("broadcast").await;
+        frames.extend(server.frames_until("broadcast").await);
+        frames.extend(server.frames_until("broadcast").await);
+        assert_eq!(broadcast_events(&frames), ["first", "second", "third"]);
+    }
+
+    #[tokio::test]
+    async fn test_push_buffer_full() {
+        let mut server = TestServer::spawn().await;
+        let client = RealtimeClient::builder(&server.url, KEY)
+            .push_buffer_capacity(1)
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let (channel, _) = joining_channel(&mut server, &client, "test-buffer-full").await;
+
+        channel.send("first", broadcast("first")).await.unwrap();
+        let result = channel.send("second", broadcast("second")).await;
+        assert!(matches!(
+            result,
+            Err(RealtimeError::PushBufferFull { event, capacity: 1, .. }) if event == "second"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_buffered_push_expires() {
+        let mut server = TestServer::spawn().await;
+        let client = RealtimeClient::builder(&server.url, KEY)
+            .push_buffer_ttl(Duration::from_millis(50))
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let (channel, join) = joining_channel(&mut server, &client, "test-buffer-ttl").await;
+
+        let handle = channel.send("expired", broadcast("expired")).await.unwrap();
+        assert!(matches!(handle.await, PushReply::TimedOut));
+
+        // the expired push is dropped from the buffer rather than sent once joined
+        server.reply(&join, "ok", json!({}));
+        let mut state = channel.state_watch();
+        state
+            .wait_for(|state| *state == ChannelState::Joined)
+            .await
+            .unwrap();
+        channel.send("sent", broadcast("sent")).await.unwrap();
+        let frames = server.frames_until("broadcast").await;
+        assert_eq!(broadcast_events(&frames), ["sent"]);
+    }
+}
@@ -0,0 +1,253 @@
+mod common;
+
+use std::time::Duration;
+
+use serde_json::json;
+use tokio::sync::mpsc;
+
+use supabase_realtime_rs::{
+    client::RealtimeClient,
+    error::RealtimeError,
+    protocol_objects::Payload,
+    reconnect_policy::{FixedDelay, NeverRetry},
+    types::{ChannelState, ConnectionState, SubscribeState},
+};
+
+use common::TestServer;
+
+const KEY: &str = "api-key";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_connect_waits_for_reconnection() {
+        let server = TestServer::spawn().await;
+        let client = RealtimeClient::builder(&server.url, KEY)
+            .reconnect_policy(FixedDelay::new(Duration::from_millis(200)))
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let mut channel = client
+            .create_channel("test-connect-reconnecting", None)
+            .await;
+        let mut channel_state = channel.state_watch();
+        channel.subscribe(None).await.unwrap();
+        channel_state
+            .wait_for(|state| *state == ChannelState::Joined)
+            .await
+            .unwrap();
+
+        let mut connection_state = client.connection_state();
+        server.drop_connection();
+        connection_state
+            .wait_for(|state| *state != ConnectionState::Open)
+            .await
+            .unwrap();
+
+        // waits for the supervisor instead of opening a connection of its own
+        client.connect().await.unwrap();
+        assert!(client.is_connected());
+        channel_state
+            .wait_for(|state| *state == ChannelState::Joined)
+            .await
+            .unwrap();
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(server.connections(), 2);
+        assert!(client.is_connected());
+        assert_eq!(*channel.state_watch().borrow(), ChannelState::Joined);
+    }
+
+    #[tokio::test]
+    async fn test_connection_lost_while_joining_is_not_a_timeout() {
+        let mut server = TestServer::spawn().await;
+        server.hold_replies("phx_join");
+        let client = RealtimeClient::builder(&server.url, KEY)
+            .reconnect_policy(FixedDelay::new(Duration::from_millis(50)))
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let mut channel = client.create_channel("test-lost-while-joining", None).await;
+
+        let (sender, mut subscribe_states) = mpsc::unbounded_channel();
+        channel
+            .subscribe(Some(Box::new(move |state| {
+                let _ = sender.send(state);
+            })))
+            .await
+            .unwrap();
+        server.next_event("phx_join").await;
+        server.drop_connection();
+
+        let join = server.next_event("phx_join").await;
+        server.reply(&join, "ok", json!({}));
+
+        assert!(matches!(
+            subscribe_states.recv().await,
+            Some(Ok(SubscribeState::Subscribed))
+        ));
+        assert_eq!(*channel.state_watch().borrow(), ChannelState::Joined);
+    }
+
+    #[tokio::test]
+    async fn test_channels_are_rejoined_after_reconnecting() {
+        let mut server = TestServer::spawn().await;
+        let client = RealtimeClient::builder(&server.url, KEY)
+            .reconnect_policy(FixedDelay::new(Duration::from_millis(50)))
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let mut channel = client.create_channel("test-rejoin", None).await;
+        let (sender, mut broadcasts) = mpsc::unbounded_channel();
+        channel
+            .on_broadcast("ping", move |payload| {
+                let _ = sender.send(payload);
+            })
+            .await;
+
+        let mut channel_state = channel.state_watch();
+        channel.subscribe(None).await.unwrap();
+        let first_join = server.next_event("phx_join").await;
+        channel_state
+            .wait_for(|state| *state == ChannelState::Joined)
+            .await
+            .unwrap();
+
+        let mut connection_state = client.connection_state();
+        server.drop_connection();
+        connection_state
+            .wait_for(|state| matches!(state, ConnectionState::Reconnecting { .. }))
+            .await
+            .unwrap();
+        connection_state
+            .wait_for(|state| *state == ConnectionState::Open)
+            .await
+            .unwrap();
+
+        let second_join = server.next_event("phx_join").await;
+        assert_eq!(second_join["topic"], "realtime:test-rejoin");
+        assert_ne!(second_join["ref"], first_join["ref"]);
+        channel_state
+            .wait_for(|state| *state == ChannelState::Joined)
+            .await
+            .unwrap();
+        assert_eq!(server.connections(), 2);
+
+        // bindings survive the reconnection
+        channel.broadcast("ping", &json!({})).await.unwrap();
+        assert!(matches!(
+            broadcasts.recv().await,
+            Some(Payload::Broadcast(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_channels_close_when_reconnecting_gives_up() {
+        let server = TestServer::spawn().await;
+        let client = RealtimeClient::builder(&server.url, KEY)
+            .reconnect_policy(NeverRetry)
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let mut channel = client.create_channel("test-give-up", None).await;
+
+        let (sender, mut subscribe_states) = mpsc::unbounded_channel();
+        let mut channel_state = channel.state_watch();
+        channel
+            .subscribe(Some(Box::new(move |state| {
+                let _ = sender.send(state);
+            })))
+            .await
+            .unwrap();
+        channel_state
+            .wait_for(|state| *state == ChannelState::Joined)
+            .await
+            .unwrap();
+        assert!(matches!(
+            subscribe_states.recv().await,
+            Some(Ok(SubscribeState::Subscribed))
+        ));
+
+        server.drop_connection();
+        channel_state
+            .wait_for(|state| *state == ChannelState::Closed)
+            .await
+            .unwrap();
+        assert!(matches!(
+            subscribe_states.recv().await,
+            Some(Ok(SubscribeState::Closed))
+        ));
+        assert_eq!(*client.connection_state().borrow(), ConnectionState::Closed);
+
+        // a closed channel can be subscribed again
+        channel.subscribe(None).await.unwrap();
+        channel_state
+            .wait_for(|state| *state == ChannelState::Joined)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_missed_heartbeat_reply_closes_connection() {
+        let mut server = TestServer::spawn().await;
+        let client = RealtimeClient::builder(&server.url, KEY)
+            .heartbeat_interval(Duration::from_millis(50))
+            .reconnect_policy(NeverRetry)
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        client.connect().await.unwrap();
+
+        for _ in 0..3 {
+            server.next_event("heartbeat").await;
+        }
+        // a heartbeat is only sent once the previous one was replied to
+        assert!(client.is_connected());
+        assert!(client.latency().is_some());
+
+        server.hold_replies("heartbeat");
+        let mut connection_state = client.connection_state();
+        tokio::time::timeout(
+            Duration::from_secs(1),
+            connection_state.wait_for(|state| *state == ConnectionState::Closed),
+        )
+        .await
+        .expect("The connection wasn't closed.")
+        .unwrap();
+        assert_eq!(server.connections(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_client_is_dropped_while_reconnecting() {
+        let server = TestServer::spawn().await;
+        let client = RealtimeClient::builder(&server.url, KEY)
+            .reconnect_policy(FixedDelay::new(Duration::from_secs(10)))
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+        let mut channel = client
+            .create_channel("test-dropped-reconnecting", None)
+            .await;
+        let mut channel_state = channel.state_watch();
+        channel.subscribe(None).await.unwrap();
+        channel_state
+            .wait_for(|state| *state == ChannelState::Joined)
+            .await
+            .unwrap();
+
+        let mut connection_state = client.connection_state();
+        server.drop_connection();
+        connection_state
+            .wait_for(|state| *state == ConnectionState::Reconnecting { attempt: 1 })
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // backing off doesn't keep the client alive
+        drop(client);
+        let result = channel.broadcast("after-drop", &json!({})).await;
+        assert!(matches!(result, Err(RealtimeError::ClientDropped)));
+    }
+}
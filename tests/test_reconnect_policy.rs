@@ -0,0 +1,40 @@
+use std::time::Duration;
+
+use supabase_realtime_rs::reconnect_policy::{
+    ExponentialBackoff, FixedDelay, NeverRetry, ReconnectPolicy,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exponential_backoff_with_jitter() {
+        let policy = ExponentialBackoff::new(Duration::from_millis(100), Duration::from_secs(1))
+            .with_max_retries(6);
+
+        assert_eq!(policy.max_delay(1), Duration::from_millis(100));
+        assert_eq!(policy.max_delay(2), Duration::from_millis(200));
+        assert_eq!(policy.max_delay(4), Duration::from_millis(800));
+        assert_eq!(policy.max_delay(5), Duration::from_secs(1));
+        assert_eq!(policy.max_delay(u32::MAX), Duration::from_secs(1));
+
+        for attempt in 1..=6 {
+            let delay = policy
+                .next_delay(attempt)
+                .expect("Policy gave up too early.");
+            assert!(delay <= policy.max_delay(attempt));
+        }
+        assert_eq!(policy.next_delay(7), None);
+    }
+
+    #[test]
+    fn test_fixed_delay_and_never_retry() {
+        let policy = FixedDelay::new(Duration::from_secs(2)).with_max_retries(2);
+        assert_eq!(policy.next_delay(1), Some(Duration::from_secs(2)));
+        assert_eq!(policy.next_delay(2), Some(Duration::from_secs(2)));
+        assert_eq!(policy.next_delay(3), None);
+
+        assert_eq!(NeverRetry.next_delay(1), None);
+    }
+}
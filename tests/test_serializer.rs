@@ -0,0 +1,235 @@
+use bytes::Bytes;
+use serde_json::{Value, json};
+use tokio_tungstenite::tungstenite::Message as TMessage;
+
+use supabase_realtime_rs::{
+    error::RealtimeError,
+    protocol_objects::{BinaryBroadcast, Broadcast, Message, Payload},
+    serializer::{decode, decode_binary, encode},
+    types::ProtocolVersion,
+};
+
+fn broadcast_message(join_ref: Option<&str>, _ref: Option<&str>) -> Message {
+    Message {
+        topic: String::from("realtime:room"),
+        payload: Payload::Broadcast(Broadcast {
+            event: String::from("cursor"),
+            payload: json!({"x": 1}),
+        }),
+        ref_field: _ref.map(String::from),
+        join_ref: join_ref.map(String::from),
+    }
+}
+
+fn heartbeat_message() -> Message {
+    decode(r#"{"topic":"phoenix","event":"heartbeat","payload":{},"ref":"3"}"#).unwrap()
+}
+
+fn binary_broadcast_message(event: &str, join_ref: Option<&str>) -> Message {
+    Message {
+        topic: String::from("realtime:room"),
+        payload: Payload::BinaryBroadcast(BinaryBroadcast {
+            event: String::from(event),
+            payload: Bytes::from_static(&[0, 159, 146, 150]),
+        }),
+        ref_field: Some(String::from("12")),
+        join_ref: join_ref.map(String::from),
+    }
+}
+
+/// A user broadcast frame sent by the server: kind 4, then the sizes of the topic, event and
+/// metadata, and the payload encoding.
+fn user_broadcast_frame(encoding: u8, payload: &[u8]) -> Bytes {
+    let mut frame = vec![4, 13, 6, 2, encoding];
+    frame.extend_from_slice(b"realtime:room");
+    frame.extend_from_slice(b"cursor");
+    frame.extend_from_slice(b"{}");
+    frame.extend_from_slice(payload);
+    Bytes::from(frame)
+}
+
+fn encode_text(message: &Message, vsn: ProtocolVersion) -> Value {
+    let Ok(TMessage::Text(text)) = encode(message, vsn) else {
+        panic!("Expected a text frame.");
+    };
+    serde_json::from_str(&text).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_v1_round_trip() {
+        let message = broadcast_message(Some("1"), None);
+
+        let frame = encode_text(&message, ProtocolVersion::V1);
+        assert_eq!(
+            frame,
+            json!({
+                "topic": "realtime:room",
+                "event": "broadcast",
+                "payload": {"event": "cursor", "payload": {"x": 1}},
+                "ref": null,
+            })
+        );
+
+        let decoded = decode(&frame.to_string()).unwrap();
+        assert_eq!(decoded.topic, "realtime:room");
+        assert_eq!(decoded.ref_field, None);
+        // V1 frames don't carry the join ref
+        assert_eq!(decoded.join_ref, None);
+        assert!(matches!(
+            decoded.payload,
+            Payload::Broadcast(Broadcast { event, .. }) if event == "cursor"
+        ));
+    }
+
+    #[test]
+    fn test_v2_round_trip() {
+        let message = broadcast_message(Some("1"), None);
+
+        let frame = encode_text(&message, ProtocolVersion::V2);
+        assert_eq!(
+            frame,
+            json!([
+                "1",
+                null,
+                "realtime:room",
+                "broadcast",
+                {"event": "cursor", "payload": {"x": 1}}
+            ])
+        );
+
+        // told apart from V1 by the leading `[`
+        let decoded = decode(&format!(" {frame}")).unwrap();
+        assert_eq!(decoded.topic, "realtime:room");
+        assert_eq!(decoded.join_ref.as_deref(), Some("1"));
+        assert_eq!(decoded.ref_field, None);
+        assert!(matches!(
+            decoded.payload,
+            Payload::Broadcast(Broadcast { event, .. }) if event == "cursor"
+        ));
+    }
+
+    #[test]
+    fn test_heartbeat_frames() {
+        let message = heartbeat_message();
+
+        assert_eq!(
+            encode_text(&message, ProtocolVersion::V1),
+            json!({"topic": "phoenix", "event": "heartbeat", "payload": {}, "ref": "3"})
+        );
+        assert_eq!(
+            encode_text(&message, ProtocolVersion::V2),
+            json!([null, "3", "phoenix", "heartbeat", {}])
+        );
+
+        let reply =
+            decode(r#"[null,"3","phoenix","phx_reply",{"status":"ok","response":{}}]"#).unwrap();
+        assert_eq!(reply.topic, "phoenix");
+        assert_eq!(reply.join_ref, None);
+        assert_eq!(reply.ref_field.as_deref(), Some("3"));
+        assert!(matches!(reply.payload, Payload::PhxReply(_)));
+    }
+
+    #[test]
+    fn test_encode_user_broadcast_push() {
+        let message = binary_broadcast_message("cursor", Some("1"));
+
+        let Ok(TMessage::Binary(frame)) = encode(&message, ProtocolVersion::V2) else {
+            panic!("Expected a binary frame.");
+        };
+        // kind 3, the sizes of the join ref, ref, topic and event, no metadata, binary encoding
+        assert_eq!(frame[..7], [3, 1, 2, 13, 6, 0, 0]);
+        assert_eq!(&frame[7..29], b"112realtime:roomcursor");
+        assert_eq!(frame[29..], [0, 159, 146, 150]);
+    }
+
+    #[test]
+    fn test_encode_user_broadcast_push_without_join_ref() {
+        let message = binary_broadcast_message("cursor", None);
+
+        let Ok(TMessage::Binary(frame)) = encode(&message, ProtocolVersion::V2) else {
+            panic!("Expected a binary frame.");
+        };
+        assert_eq!(frame[..7], [3, 0, 2, 13, 6, 0, 0]);
+        assert_eq!(&frame[7..28], b"12realtime:roomcursor");
+    }
+
+    #[test]
+    fn test_encode_binary_broadcast_errors() {
+        let message = binary_broadcast_message("cursor", Some("1"));
+        assert!(matches!(
+            encode(&message, ProtocolVersion::V1),
+            Err(RealtimeError::BinaryBroadcastUnsupported)
+        ));
+
+        // sizes are a single byte
+        let message = binary_broadcast_message(&"e".repeat(256), Some("1"));
+        assert!(matches!(
+            encode(&message, ProtocolVersion::V2),
+            Err(RealtimeError::BinaryFrameError { .. })
+        ));
+
+        let message = binary_broadcast_message(&"e".repeat(255), Some("1"));
+        assert!(encode(&message, ProtocolVersion::V2).is_ok());
+    }
+
+    #[test]
+    fn test_decode_binary_user_broadcast() {
+        let message = decode_binary(user_broadcast_frame(0, &[0, 159, 146, 150])).unwrap();
+
+        assert_eq!(message.topic, "realtime:room");
+        assert_eq!(message.ref_field, None);
+        let Payload::BinaryBroadcast(broadcast) = message.payload else {
+            panic!("Expected a binary broadcast.");
+        };
+        assert_eq!(broadcast.event, "cursor");
+        // the metadata is skipped
+        assert_eq!(broadcast.payload, Bytes::from_static(&[0, 159, 146, 150]));
+    }
+
+    #[test]
+    fn test_decode_json_user_broadcast() {
+        let message = decode_binary(user_broadcast_frame(1, br#"{"x":1}"#)).unwrap();
+
+        let Payload::Broadcast(broadcast) = message.payload else {
+            panic!("Expected a JSON broadcast.");
+        };
+        assert_eq!(broadcast.event, "cursor");
+        assert_eq!(broadcast.payload, json!({"x": 1}));
+    }
+
+    #[test]
+    fn test_decode_invalid_binary_frames() {
+        let frame = user_broadcast_frame(0, &[]);
+        for size in [0, 1, 4, 10, 20] {
+            assert!(
+                matches!(
+                    decode_binary(frame.slice(..size)),
+                    Err(RealtimeError::BinaryFrameError { .. })
+                ),
+                "frame truncated to {size} bytes"
+            );
+        }
+
+        let mut unknown_kind = frame.to_vec();
+        unknown_kind[0] = 9;
+        assert!(decode_binary(Bytes::from(unknown_kind)).is_err());
+
+        let mut unknown_encoding = frame.to_vec();
+        unknown_encoding[4] = 7;
+        assert!(decode_binary(Bytes::from(unknown_encoding)).is_err());
+
+        assert!(matches!(
+            decode_binary(user_broadcast_frame(1, b"{invalid")),
+            Err(RealtimeError::BinaryFrameError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_decode_invalid_v2_frame() {
+        assert!(decode(r#"["1","2","realtime:room"]"#).is_err());
+    }
+}
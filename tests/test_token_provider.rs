@@ -0,0 +1,47 @@
+use std::time::{Duration, SystemTime};
+
+use data_encoding::BASE64URL_NOPAD;
+
+use supabase_realtime_rs::{
+    client::RealtimeClient, error::RealtimeError, token_provider::expires_at,
+};
+
+fn jwt(claims: &str) -> String {
+    format!(
+        "{}.{}.signature",
+        BASE64URL_NOPAD.encode(br#"{"alg":"HS256","typ":"JWT"}"#),
+        BASE64URL_NOPAD.encode(claims.as_bytes())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expires_at() {
+        let token = jwt(r#"{"role":"authenticated","exp":1700000000}"#);
+        assert_eq!(
+            expires_at(&token),
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1700000000))
+        );
+
+        assert_eq!(expires_at(&jwt(r#"{"role":"anon"}"#)), None);
+        assert_eq!(expires_at("api-key"), None);
+    }
+
+    #[tokio::test]
+    async fn test_connect_fails_without_token() {
+        let client = RealtimeClient::builder("http://127.0.0.1:54321", "api-key")
+            .token_provider(|| async { Err("session expired".into()) })
+            .logger(|_, _| {})
+            .build()
+            .expect("Error while building client.");
+
+        let result = client.connect().await;
+        assert!(matches!(
+            result,
+            Err(RealtimeError::TokenProviderError { .. })
+        ));
+    }
+}